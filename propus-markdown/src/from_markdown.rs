@@ -0,0 +1,387 @@
+use std::borrow::Cow;
+
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options as ParserOptions, Parser, Tag, TagEnd};
+use tejat::gemtext::{Heading, Link, Preformatted, RawLine};
+
+/// Where the link lines for inline Markdown links are placed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LinkPlacement {
+    /// After the top-level block (paragraph, list, quote, ...) containing the links.
+    #[default]
+    Block,
+    /// All together at the end of the document.
+    Document,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Options {
+    pub link_placement: LinkPlacement,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Container {
+    Quote,
+    /// A list and the number of its next item, if it is ordered.
+    List(Option<u64>),
+    Item,
+}
+
+/// A link whose text is still being collected, starting at `start` in the text buffer.
+struct OpenLink {
+    url: String,
+    start: usize,
+    is_image: bool,
+}
+
+#[derive(Default)]
+struct Converter {
+    options: Options,
+    lines: Vec<RawLine<'static>>,
+    /// Inline text of the current line.
+    text: String,
+    /// Whether `text` contains anything besides link text.
+    has_plain_text: bool,
+    containers: Vec<Container>,
+    /// The number of open block-level elements.
+    depth: usize,
+    open_links: Vec<OpenLink>,
+    links: Vec<(String, String)>,
+    code: Option<(Option<String>, String)>,
+    table: Option<Vec<Vec<String>>>,
+}
+
+impl Converter {
+    fn push_line(&mut self, line: RawLine<'static>) {
+        self.lines.push(line);
+    }
+
+    /// Starts a block-level element, which ends the current line.
+    fn start_block(&mut self) {
+        self.flush_text();
+        self.enter_block();
+    }
+
+    fn enter_block(&mut self) {
+        if self.depth == 0 && !self.lines.is_empty() {
+            self.push_line(RawLine::Text(Cow::Borrowed("")));
+        }
+        self.depth += 1;
+    }
+
+    fn end_block(&mut self) {
+        self.depth -= 1;
+        if self.depth == 0 && self.options.link_placement == LinkPlacement::Block {
+            self.flush_links();
+        }
+    }
+
+    fn flush_links(&mut self) {
+        for (url, text) in std::mem::take(&mut self.links) {
+            if url.is_empty() {
+                continue;
+            }
+            // Link targets end at whitespace, which is allowed in Markdown link destinations.
+            let url = url.replace(' ', "%20");
+            let text = (!text.is_empty()).then_some(text);
+            if let Ok(link) = Link::new(url, text) {
+                self.push_line(RawLine::Link(link.into_static()));
+            }
+        }
+    }
+
+    /// Writes the inline text collected so far as a line of the kind its container requires.
+    ///
+    /// Lines that only consist of links are dropped, as the link lines replace them.
+    fn flush_text(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        let text = text.trim();
+        if text.is_empty() || !std::mem::take(&mut self.has_plain_text) {
+            return;
+        }
+
+        let text = Cow::Owned(text.to_string());
+        let line = match self.containers.iter().rev().find(|c| !matches!(c, Container::List(_))) {
+            Some(Container::Item) => RawLine::ListItem(text),
+            Some(Container::Quote) => RawLine::Blockquote(text),
+            _ => RawLine::Text(text),
+        };
+        self.push_line(line);
+    }
+
+    fn start(&mut self, tag: Tag<'_>) {
+        match tag {
+            // The paragraphs of a loose list item share the item's line.
+            Tag::Paragraph => self.enter_block(),
+            Tag::Heading { .. } | Tag::HtmlBlock | Tag::MetadataBlock(_) => self.start_block(),
+            Tag::BlockQuote(_) => {
+                self.start_block();
+                self.containers.push(Container::Quote);
+            }
+            Tag::CodeBlock(kind) => {
+                self.start_block();
+                let alt_text = match kind {
+                    CodeBlockKind::Fenced(info) if !info.is_empty() => Some(info.to_string()),
+                    _ => None,
+                };
+                self.code = Some((alt_text, String::new()));
+            }
+            Tag::List(first) => {
+                // Nested lists are flattened, so the parent item ends here.
+                self.start_block();
+                self.containers.push(Container::List(first));
+            }
+            Tag::Item => {
+                self.start_block();
+                self.containers.push(Container::Item);
+                if let Some(Container::List(Some(number))) = self.containers.iter().rev().nth(1).copied() {
+                    self.push_text(&format!("{}. ", number));
+                    let len = self.containers.len();
+                    self.containers[len - 2] = Container::List(Some(number + 1));
+                }
+            }
+            Tag::Table(_) => {
+                self.start_block();
+                self.table = Some(Vec::new());
+            }
+            Tag::TableHead | Tag::TableRow => {
+                if let Some(rows) = &mut self.table {
+                    rows.push(Vec::new());
+                }
+            }
+            Tag::Link { dest_url, .. } => self.open_links.push(OpenLink {
+                url: dest_url.to_string(),
+                start: self.text.len(),
+                is_image: false,
+            }),
+            Tag::Image { dest_url, .. } => self.open_links.push(OpenLink {
+                url: dest_url.to_string(),
+                start: self.text.len(),
+                is_image: true,
+            }),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                if self.containers.last() == Some(&Container::Item) {
+                    // Keep paragraphs of a loose list item on the item's line.
+                    self.text.push(' ');
+                } else {
+                    self.flush_text();
+                }
+                self.end_block();
+            }
+            TagEnd::Heading(level) => {
+                let text = Cow::Owned(std::mem::take(&mut self.text).trim().to_string());
+                let heading = match level {
+                    HeadingLevel::H1 => Heading::H1(text),
+                    HeadingLevel::H2 => Heading::H2(text),
+                    _ => Heading::H3(text),
+                };
+                self.push_line(RawLine::Heading(heading));
+                self.end_block();
+            }
+            TagEnd::BlockQuote(_) | TagEnd::List(_) => {
+                self.flush_text();
+                self.containers.pop();
+                self.end_block();
+            }
+            TagEnd::Item => {
+                self.flush_text();
+                self.containers.pop();
+                self.end_block();
+            }
+            TagEnd::CodeBlock => {
+                if let Some((alt_text, text)) = self.code.take() {
+                    let text = text.strip_suffix('\n').unwrap_or(&text).to_string();
+                    self.push_line(RawLine::Preformatted(Preformatted {
+                        alt_text: alt_text.map(Cow::Owned),
+                        text: Cow::Owned(text),
+                    }));
+                }
+                self.end_block();
+            }
+            TagEnd::TableCell => {
+                let cell = std::mem::take(&mut self.text).trim().to_string();
+                if let Some(row) = self.table.as_mut().and_then(|rows| rows.last_mut()) {
+                    row.push(cell);
+                }
+            }
+            TagEnd::Table => {
+                if let Some(rows) = self.table.take() {
+                    self.push_line(RawLine::Preformatted(Preformatted {
+                        alt_text: None,
+                        text: Cow::Owned(format_table(&rows)),
+                    }));
+                }
+                self.end_block();
+            }
+            TagEnd::HtmlBlock | TagEnd::MetadataBlock(_) => {
+                self.text.clear();
+                self.end_block();
+            }
+            TagEnd::Link | TagEnd::Image => {
+                if let Some(link) = self.open_links.pop() {
+                    let text = self.text[link.start..].trim().to_string();
+                    if link.is_image && text.is_empty() {
+                        // Images without alt text leave nothing behind, not even the space before them.
+                        let len = self.text.trim_end().len();
+                        self.text.truncate(len);
+                    }
+                    self.links.push((link.url, text));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if self.open_links.is_empty() && !text.trim().is_empty() {
+            self.has_plain_text = true;
+        }
+        self.text.push_str(text);
+    }
+
+    fn event(&mut self, event: Event<'_>) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => match &mut self.code {
+                Some((_, code)) => code.push_str(&text),
+                None => self.push_text(&text),
+            },
+            Event::Code(code) => self.push_text(&format!("`{}`", code)),
+            Event::SoftBreak => self.text.push(' '),
+            Event::HardBreak => self.flush_text(),
+            Event::Rule => {
+                self.start_block();
+                self.push_line(RawLine::Text(Cow::Borrowed("---")));
+                self.end_block();
+            }
+            Event::TaskListMarker(checked) => self.push_text(if checked { "[x] " } else { "[ ] " }),
+            _ => {}
+        }
+    }
+
+    fn finish(mut self) -> Vec<RawLine<'static>> {
+        if !self.links.is_empty() {
+            if !self.lines.is_empty() {
+                self.push_line(RawLine::Text(Cow::Borrowed("")));
+            }
+            self.flush_links();
+        }
+        self.lines
+    }
+}
+
+fn format_table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let format_row = |row: &Vec<String>| {
+        let cells: Vec<String> = widths.iter().enumerate()
+            .map(|(column, width)| {
+                let cell = row.get(column).map_or("", String::as_str);
+                format!("{}{}", cell, " ".repeat(width - cell.chars().count()))
+            })
+            .collect();
+        cells.join(" | ").trim_end().to_string()
+    };
+
+    let mut lines = Vec::with_capacity(rows.len() + 1);
+    for (index, row) in rows.iter().enumerate() {
+        lines.push(format_row(row));
+        if index == 0 {
+            let separators: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+            lines.push(separators.join("-+-"));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Converts CommonMark (plus tables and strikethrough) to gemtext.
+///
+/// Inline formatting other than code spans is dropped, inline links become link lines placed
+/// according to [`Options::link_placement`], nested lists are flattened, and tables become
+/// preformatted blocks. Raw HTML is dropped.
+pub fn markdown_to_gemtext(markdown: &str, options: &Options) -> Vec<RawLine<'static>> {
+    let parser = Parser::new_ext(markdown, ParserOptions::ENABLE_TABLES | ParserOptions::ENABLE_STRIKETHROUGH);
+    let mut converter = Converter {
+        options: options.clone(),
+        ..Converter::default()
+    };
+    for event in parser {
+        converter.event(event);
+    }
+    converter.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tejat::gemtext::serialize_lines;
+    use test_case::test_case;
+
+    fn convert(markdown: &str, link_placement: LinkPlacement) -> String {
+        serialize_lines(&markdown_to_gemtext(markdown, &Options { link_placement }))
+    }
+
+    #[test_case("# One\n\n## Two\n\n#### Four\n", "# One\n\n## Two\n\n### Four\n" ; "headings")]
+    #[test_case("Some *emphasis*\nand `code`.\n\nNext  \nline\n", "Some emphasis and `code`.\n\nNext\nline\n" ; "paragraphs")]
+    #[test_case("- a\n- b\n  - nested\n- c\n", "* a\n* b\n* nested\n* c\n" ; "nested lists are flattened")]
+    #[test_case("3. three\n4. four\n", "* 3. three\n* 4. four\n" ; "ordered lists")]
+    #[test_case("- a\n\n  more\n- b\n", "* a more\n* b\n" ; "loose list items")]
+    #[test_case("> quoted\n> text\n>\n> second\n", "> quoted text\n> second\n" ; "quotes")]
+    #[test_case("```rust\nfn main() {}\n```\n", "```rust\nfn main() {}\n```\n" ; "code blocks")]
+    #[test_case("    indented\n", "```\nindented\n```\n" ; "indented code blocks")]
+    #[test_case("a\n\n---\n\nb\n", "a\n\n---\n\nb\n" ; "rules")]
+    #[test_case("<div>html</div>\n\ntext\n", "text\n" ; "html is dropped")]
+    #[test_case(
+        "| a | long header |\n|---|---|\n| value | x |\n",
+        "```\na     | long header\n------+------------\nvalue | x\n```\n" ;
+        "tables"
+    )]
+    #[test_case("- item\n\n  ```\n  code\n  ```\n", "* item\n```\ncode\n```\n" ; "code block in list item")]
+    #[test_case("- item\n  > quoted\n", "* item\n> quoted\n" ; "quote in list item")]
+    #[test_case("A ![](dot.png) dot\n", "A dot\n=> dot.png\n" ; "image without alt text")]
+    fn test_convert(markdown: &str, expected: &str) {
+        assert_eq!(convert(markdown, LinkPlacement::Block), expected);
+    }
+
+    #[test]
+    fn test_links_per_block() {
+        let markdown = "See [the site](gemini://example.com/) and ![a cat](cat.png).\n\n\
+                        - [item link](</item path>)\n\nEnd.\n";
+        assert_eq!(
+            convert(markdown, LinkPlacement::Block),
+            "See the site and a cat.\n\
+             => gemini://example.com/ the site\n\
+             => cat.png a cat\n\n\
+             => /item%20path item link\n\n\
+             End.\n"
+        );
+    }
+
+    #[test]
+    fn test_links_per_document() {
+        let markdown = "# [Title](/title)\n\nSee <https://example.com>.\n";
+        assert_eq!(
+            convert(markdown, LinkPlacement::Document),
+            "# Title\n\n\
+             See https://example.com.\n\n\
+             => /title Title\n\
+             => https://example.com/ https://example.com\n"
+        );
+    }
+}
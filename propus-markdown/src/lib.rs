@@ -0,0 +1,7 @@
+//! Conversion between gemtext and [CommonMark](https://commonmark.org) Markdown.
+
+mod from_markdown;
+mod to_markdown;
+
+pub use from_markdown::{markdown_to_gemtext, LinkPlacement, Options};
+pub use to_markdown::gemtext_to_markdown;
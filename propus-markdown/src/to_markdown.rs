@@ -0,0 +1,149 @@
+use tejat::gemtext::{Block, Document, Heading, Link, Preformatted, RawLine};
+
+/// Escapes the characters that could start Markdown syntax inside `text`.
+///
+/// Leading whitespace is dropped: Markdown ignores a little of it and turns more into an indented
+/// code block, and it could also make a list marker after it count.
+fn escape(text: &str) -> String {
+    let text = text.trim_start();
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '!' | '|' | '~' | '&') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    // These only start a block at the beginning of a line.
+    if let Some(rest) = escaped.strip_prefix(['-', '+', '=']) {
+        escaped = format!("\\{}{}", &escaped[..1], rest);
+    } else if let Some(index) = escaped.find(|c: char| !c.is_ascii_digit()) {
+        if index > 0 && escaped[index..].starts_with(['.', ')']) {
+            escaped.insert(index, '\\');
+        }
+    }
+    escaped
+}
+
+fn write_link(out: &mut String, link: &Link<'_>) {
+    let target = link.target().as_str();
+    let destination = if target.contains(['(', ')', '<', '>']) || target.is_empty() {
+        format!("<{}>", target.replace('<', "%3C").replace('>', "%3E"))
+    } else {
+        target.to_string()
+    };
+    let text = escape(link.text().unwrap_or(target));
+    out.push_str(&format!("[{}]({})\n", text, destination));
+}
+
+fn write_preformatted(out: &mut String, pre: &Preformatted<'_>) {
+    let alt_text = pre.alt_text.as_deref().unwrap_or("");
+    let fence_char = if alt_text.contains('`') { '~' } else { '`' };
+
+    // The fence has to be longer than any run of fence characters in the text.
+    let longest_run = pre.text.lines()
+        .map(|line| line.trim_start().chars().take_while(|c| *c == fence_char).count())
+        .max()
+        .unwrap_or(0);
+    let fence = fence_char.to_string().repeat(longest_run.max(2) + 1);
+
+    out.push_str(&format!("{}{}\n", fence, alt_text));
+    if !pre.text.is_empty() {
+        out.push_str(&pre.text);
+        out.push('\n');
+    }
+    out.push_str(&fence);
+    out.push('\n');
+}
+
+/// Converts gemtext to CommonMark.
+///
+/// Every text line becomes its own paragraph, since gemtext has no soft line breaks, and every
+/// link line becomes a paragraph containing only the link. Text is escaped so that it is never
+/// interpreted as Markdown syntax.
+pub fn gemtext_to_markdown(lines: &[RawLine<'_>]) -> String {
+    let document = Document::from(lines.to_vec());
+    let mut paragraphs = Vec::new();
+
+    for block in document.blocks() {
+        let mut out = String::new();
+        match block {
+            Block::Blank(_) => continue,
+            Block::Paragraph(lines) => {
+                let lines: Vec<_> = lines.iter().map(|line| escape(line)).collect();
+                out = lines.join("\n\n") + "\n";
+            }
+            Block::Heading(heading) => {
+                let prefix = match heading {
+                    Heading::H1(_) => "#",
+                    Heading::H2(_) => "##",
+                    Heading::H3(_) => "###",
+                };
+                out = format!("{} {}\n", prefix, escape(heading.text()));
+            }
+            Block::LinkGroup(links) => {
+                for (index, link) in links.iter().enumerate() {
+                    if index > 0 {
+                        out.push('\n');
+                    }
+                    write_link(&mut out, link);
+                }
+            }
+            Block::List(items) => {
+                for item in items {
+                    out.push_str(&format!("- {}\n", escape(item)));
+                }
+            }
+            Block::Quote(lines) => {
+                for (index, line) in lines.iter().enumerate() {
+                    if index > 0 {
+                        out.push_str(">\n");
+                    }
+                    out.push_str(&format!("> {}\n", escape(line)));
+                }
+            }
+            Block::Preformatted(pre) => write_preformatted(&mut out, pre),
+        }
+        paragraphs.push(out);
+    }
+
+    paragraphs.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{markdown_to_gemtext, Options};
+    use pretty_assertions::assert_eq;
+    use tejat::gemtext::parse_lines;
+    use test_case::test_case;
+
+    #[test_case("# One\n## Two\n### Three\n", "# One\n\n## Two\n\n### Three\n" ; "headings")]
+    #[test_case("first\nsecond\n\n\nthird\n", "first\n\nsecond\n\nthird\n" ; "text lines are paragraphs")]
+    #[test_case("* a\n* b\n", "- a\n- b\n" ; "lists")]
+    #[test_case("> a\n> b\n", "> a\n>\n> b\n" ; "quotes")]
+    #[test_case(
+        "=> gemini://example.com Example\n=> /a(b) /a(b)\n",
+        "[Example](gemini://example.com)\n\n[/a(b)](</a(b)>)\n" ;
+        "links"
+    )]
+    #[test_case("```rust\nfn main() {}\n```\n", "```rust\nfn main() {}\n```\n" ; "preformatted")]
+    #[test_case("```\n ````\n```\n", "`````\n ````\n`````\n" ; "fence longer than text")]
+    #[test_case("```a`b\ncode\n```\n", "~~~a`b\ncode\n~~~\n" ; "backtick in alt text")]
+    #[test_case("*not emphasis* [x](y) <b>\n", "\\*not emphasis\\* \\[x\\](y) \\<b\\>\n" ; "inline syntax is escaped")]
+    #[test_case("- not a list\n1. nor this\n", "\\- not a list\n\n1\\. nor this\n" ; "block syntax is escaped")]
+    #[test_case("    not code\n", "not code\n" ; "leading spaces are dropped")]
+    #[test_case("  - not a list\n* \t1. nor this\n", "\\- not a list\n\n- 1\\. nor this\n" ; "indented block syntax is escaped")]
+    #[test_case(" # not a heading\n", "\\# not a heading\n" ; "escaped gemtext")]
+    fn test_convert(gemtext: &str, expected: &str) {
+        assert_eq!(gemtext_to_markdown(&parse_lines(gemtext)), expected);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let gemtext = "# Title\n\nSome *text* with `ticks`.\n\n=> gemini://example.com/ Example\n\n\
+                       * one\n* two\n\n> quote\n\n```alt\ncode\n```\n";
+        let markdown = gemtext_to_markdown(&parse_lines(gemtext));
+        assert_eq!(markdown_to_gemtext(&markdown, &Options::default()), parse_lines(gemtext));
+    }
+}
@@ -0,0 +1,338 @@
+//! [`tokio_util::codec`] implementations of the Gemini protocol, for use with
+//! [`Framed`](tokio_util::codec::Framed) over any `AsyncRead + AsyncWrite`, usually a TLS stream.
+//!
+//! [`ServerCodec`] decodes a [`Request`] and encodes [`ResponseFrame`]s, and [`ClientCodec`]
+//! does the reverse. Both are thin wrappers around the state machines in [`proto`], so they
+//! follow the same rules, e.g. only one request per connection.
+
+use std::io;
+
+use bytes::{Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::proto::{self, Event};
+use crate::request::Request;
+use crate::status::Status;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Protocol(#[from] proto::Error),
+    /// The response body is larger than the limit set with [`ClientCodec::with_body_limit`].
+    #[error("response body is larger than {limit} bytes")]
+    BodyTooLarge { limit: usize },
+}
+
+/// A part of a response: the header, followed by the body in any number of frames.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ResponseFrame {
+    Header(Status),
+    Body(Bytes),
+}
+
+impl From<Status> for ResponseFrame {
+    fn from(status: Status) -> Self {
+        Self::Header(status)
+    }
+}
+
+impl From<Bytes> for ResponseFrame {
+    fn from(body: Bytes) -> Self {
+        Self::Body(body)
+    }
+}
+
+/// The server side of a connection: decodes the request and encodes the response.
+#[derive(Debug, Default)]
+pub struct ServerCodec {
+    server: proto::Server,
+}
+
+impl ServerCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn next_request(&mut self) -> Result<Option<Request>, Error> {
+        while let Some(event) = self.server.poll_event() {
+            match event {
+                Event::RequestReceived(request) => return Ok(Some(request)),
+                Event::ProtocolError(error) => return Err(error.into()),
+                Event::HeaderReceived(_) | Event::BodyChunk(_) | Event::Closed => {}
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Decoder for ServerCodec {
+    type Item = Request;
+    type Error = Error;
+
+    /// Decodes the request. An invalid request is returned as an error, after which a response
+    /// header, usually `59`, can still be encoded.
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Request>, Error> {
+        if !src.is_empty() {
+            self.server.receive(&src.split());
+        }
+        self.next_request()
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Request>, Error> {
+        match self.decode(src)? {
+            Some(request) => Ok(Some(request)),
+            None => {
+                self.server.receive_eof();
+                self.next_request()
+            }
+        }
+    }
+}
+
+impl Encoder<ResponseFrame> for ServerCodec {
+    type Error = Error;
+
+    /// Encodes a part of the response. The header must come first, and only successful
+    /// responses can have a body.
+    fn encode(&mut self, frame: ResponseFrame, dst: &mut BytesMut) -> Result<(), Error> {
+        let bytes = match frame {
+            ResponseFrame::Header(status) => self.server.send_header(&status)?,
+            ResponseFrame::Body(body) => self.server.send_body(&body)?,
+        };
+        dst.extend_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+/// The client side of a connection: encodes the request and decodes the response.
+#[derive(Debug, Default)]
+pub struct ClientCodec {
+    client: proto::Client,
+    body_limit: Option<usize>,
+    received: usize,
+}
+
+impl ClientCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails decoding once the response body is larger than `limit` bytes.
+    pub fn with_body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = Some(limit);
+        self
+    }
+
+    fn next_frame(&mut self) -> Result<Option<ResponseFrame>, Error> {
+        while let Some(event) = self.client.poll_event() {
+            match event {
+                Event::HeaderReceived(status) => return Ok(Some(ResponseFrame::Header(status))),
+                Event::BodyChunk(chunk) => {
+                    self.received += chunk.len();
+                    return match self.body_limit {
+                        Some(limit) if self.received > limit => Err(Error::BodyTooLarge { limit }),
+                        _ => Ok(Some(ResponseFrame::Body(chunk.into()))),
+                    };
+                }
+                Event::ProtocolError(error) => return Err(error.into()),
+                Event::RequestReceived(_) | Event::Closed => {}
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Decoder for ClientCodec {
+    type Item = ResponseFrame;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<ResponseFrame>, Error> {
+        if !src.is_empty() {
+            self.client.receive(&src.split());
+        }
+        self.next_frame()
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<ResponseFrame>, Error> {
+        match self.decode(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None => {
+                self.client.receive_eof();
+                self.next_frame()
+            }
+        }
+    }
+}
+
+impl Encoder<Request> for ClientCodec {
+    type Error = Error;
+
+    /// Encodes the request. Only one request can be sent per connection.
+    fn encode(&mut self, request: Request, dst: &mut BytesMut) -> Result<(), Error> {
+        dst.extend_from_slice(&self.client.send_request(&request)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request;
+    use crate::status::{self, MAX_META_LENGTH};
+    use crate::uri::GeminiUrl;
+    use futures::{SinkExt, StreamExt};
+    use pretty_assertions::assert_eq;
+    use tokio::io::{duplex, AsyncWriteExt, DuplexStream};
+    use tokio_util::codec::{Framed, FramedRead};
+
+    fn request() -> Request {
+        Request::from("gemini://example.com/".parse::<GeminiUrl>().unwrap())
+    }
+
+    /// Connects a client and a server. With a small buffer, frames arrive in pieces and writes
+    /// wait for the other side to read.
+    fn connect(buffer_size: usize) -> (Framed<DuplexStream, ClientCodec>, Framed<DuplexStream, ServerCodec>) {
+        let (client, server) = duplex(buffer_size);
+        (Framed::new(client, ClientCodec::new()), Framed::new(server, ServerCodec::new()))
+    }
+
+    #[tokio::test]
+    async fn test_exchange() {
+        let (mut client, mut server) = connect(8);
+
+        let serve = async move {
+            let request = server.next().await.unwrap().unwrap();
+            assert_eq!(request, self::request());
+            server.send("20 text/gemini".parse::<Status>().unwrap().into()).await.unwrap();
+            server.send(Bytes::from_static(b"# Hello\n").into()).await.unwrap();
+            server.send(Bytes::from_static(b"world\n").into()).await.unwrap();
+            server.close().await.unwrap();
+        };
+        let fetch = async move {
+            client.send(request()).await.unwrap();
+            let header = client.next().await.unwrap().unwrap();
+            let mut body = Vec::new();
+            while let Some(frame) = client.next().await {
+                match frame.unwrap() {
+                    ResponseFrame::Body(chunk) => body.extend_from_slice(&chunk),
+                    frame => panic!("expected a body frame, got {:?}", frame),
+                }
+            }
+            (header, body)
+        };
+
+        let ((), (header, body)) = tokio::join!(serve, fetch);
+        assert_eq!(header, ResponseFrame::Header("20 text/gemini".parse().unwrap()));
+        assert_eq!(body, b"# Hello\nworld\n");
+    }
+
+    #[tokio::test]
+    async fn test_response_without_body() {
+        let (mut client, mut server) = connect(4096);
+        client.send(request()).await.unwrap();
+        server.next().await.unwrap().unwrap();
+        server.send("51 Not found".parse::<Status>().unwrap().into()).await.unwrap();
+        assert!(matches!(
+            server.send(Bytes::from_static(b"body").into()).await,
+            Err(Error::Protocol(proto::Error::InvalidState))
+        ));
+        drop(server);
+
+        assert_eq!(client.next().await.unwrap().unwrap(), ResponseFrame::Header("51 Not found".parse().unwrap()));
+        assert!(client.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_single_request() {
+        let (mut client, _server) = connect(4096);
+        client.send(request()).await.unwrap();
+        assert!(matches!(client.send(request()).await, Err(Error::Protocol(proto::Error::InvalidState))));
+    }
+
+    #[tokio::test]
+    async fn test_server_rejects_invalid_requests() {
+        let (mut client, server) = duplex(4096);
+        let mut server = Framed::new(server, ServerCodec::new());
+
+        client.write_all(&[b'a'; 2000]).await.unwrap();
+        assert!(matches!(
+            server.next().await,
+            Some(Err(Error::Protocol(proto::Error::InvalidRequest(request::Error::TooLong))))
+        ));
+        server.send("59 Bad request".parse::<Status>().unwrap().into()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_server_rejects_data_after_request() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(b"gemini://example.com/\r\nmore").await.unwrap();
+        drop(client);
+
+        let mut server = FramedRead::new(server, ServerCodec::new());
+        assert_eq!(server.next().await.unwrap().unwrap(), request());
+        assert!(matches!(server.next().await, Some(Err(Error::Protocol(proto::Error::UnexpectedData)))));
+    }
+
+    #[tokio::test]
+    async fn test_server_closed_early() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(b"gemini://example.com/").await.unwrap();
+        drop(client);
+
+        let mut server = FramedRead::new(server, ServerCodec::new());
+        assert!(matches!(server.next().await, Some(Err(Error::Protocol(proto::Error::ClosedEarly)))));
+    }
+
+    /// Sends a request with `codec` and answers it with `response`.
+    async fn fetch(codec: ClientCodec, response: &[u8]) -> (Framed<DuplexStream, ClientCodec>, DuplexStream) {
+        let (client, mut server) = duplex(4096);
+        let mut client = Framed::new(client, codec);
+        client.send(request()).await.unwrap();
+        server.write_all(response).await.unwrap();
+        (client, server)
+    }
+
+    #[tokio::test]
+    async fn test_client_errors() {
+        let (mut client, _server) = fetch(ClientCodec::new().with_body_limit(4), b"20 text/gemini\r\n12345").await;
+        client.next().await.unwrap().unwrap();
+        assert!(matches!(client.next().await, Some(Err(Error::BodyTooLarge { limit: 4 }))));
+
+        let (mut client, _server) = fetch(ClientCodec::new(), b"99 Nope\r\n").await;
+        assert!(matches!(
+            client.next().await,
+            Some(Err(Error::Protocol(proto::Error::InvalidHeader(status::Error::UnknownCode(99)))))
+        ));
+
+        let (mut client, _server) = fetch(ClientCodec::new(), b"20 text/gemini\nbody").await;
+        assert!(matches!(
+            client.next().await,
+            Some(Err(Error::Protocol(proto::Error::InvalidHeader(status::Error::BareLf))))
+        ));
+
+        let (mut client, server) = fetch(ClientCodec::new(), b"20 text/gemini").await;
+        drop(server);
+        assert!(matches!(client.next().await, Some(Err(Error::Protocol(proto::Error::ClosedEarly)))));
+    }
+
+    #[tokio::test]
+    async fn test_client_unknown_code() {
+        let (mut client, server) = fetch(ClientCodec::new(), b"45 Busy\r\n").await;
+        drop(server);
+        assert_eq!(client.next().await.unwrap().unwrap(), ResponseFrame::Header("40 Busy".parse().unwrap()));
+        assert!(client.next().await.is_none());
+    }
+
+    #[test]
+    fn test_header_too_long() {
+        let message = "a".repeat(MAX_META_LENGTH + 1);
+        let status = Status::PermanentFailure(status::PermanentFailure::NotFound { message });
+        let mut codec = ServerCodec::new();
+        assert!(matches!(
+            codec.encode(status.into(), &mut BytesMut::new()),
+            Err(Error::Protocol(proto::Error::InvalidHeader(status::Error::MetaTooLong)))
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+//! [`GeminiMime`], a MIME type with accessors for the parameters Gemini defines.
+
+use std::{fmt, str::FromStr};
+
+use mime::{FromStrError, Mime, Name, CHARSET, TEXT};
+
+const LANG: &str = "lang";
+
+#[derive(Debug, Eq, PartialEq, thiserror::Error)]
+pub enum Error {
+    /// A language tag contains anything other than ASCII letters, digits and `-`.
+    #[error("invalid language tag {0:?}")]
+    InvalidLanguageTag(String),
+    /// The charset is not a valid MIME parameter value without quoting.
+    #[error("invalid charset {0:?}")]
+    InvalidCharset(String),
+    /// Setting a parameter resulted in an invalid MIME type.
+    #[error("invalid MIME type {0:?}")]
+    InvalidMime(String),
+}
+
+/// The MIME type of a successful response.
+///
+/// Besides `charset`, Gemini defines a `lang` parameter for `text/gemini`: a comma-separated list
+/// of BCP47 language tags, like `text/gemini; lang=en,fr`. Such unquoted lists are not valid
+/// MIME parameters, so they are quoted for parsing and unquoted again when displayed.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct GeminiMime(Mime);
+
+impl GeminiMime {
+    pub fn new(mime: Mime) -> Self {
+        Self(mime)
+    }
+
+    /// `text/gemini`, without any parameters.
+    pub fn gemtext() -> Self {
+        Self("text/gemini".parse().expect("text/gemini should be a valid MIME type"))
+    }
+
+    pub fn as_mime(&self) -> &Mime {
+        &self.0
+    }
+
+    pub fn into_mime(self) -> Mime {
+        self.0
+    }
+
+    /// The type and subtype, without parameters, e.g. `text/gemini`.
+    pub fn essence(&self) -> &str {
+        self.0.essence_str()
+    }
+
+    pub fn is_gemtext(&self) -> bool {
+        crate::mime_is_gemtext(&self.0)
+    }
+
+    pub fn is_text(&self) -> bool {
+        self.0.type_() == TEXT
+    }
+
+    /// The charset of the body, which is UTF-8 if there is no `charset` parameter.
+    pub fn charset(&self) -> Name<'_> {
+        crate::mime_charset(&self.0)
+    }
+
+    /// The language tags in the `lang` parameter, which is empty if there is none.
+    pub fn lang(&self) -> Vec<&str> {
+        match self.0.get_param(LANG) {
+            Some(lang) => lang.as_str().split(',').map(str::trim).filter(|tag| !tag.is_empty()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Sets the `lang` parameter to `tags`, or removes it if there are none.
+    ///
+    /// Fails if a tag contains anything other than ASCII letters, digits and `-`.
+    pub fn with_lang<I>(self, tags: I) -> Result<Self, Error>
+        where I: IntoIterator,
+              I::Item: AsRef<str>,
+    {
+        let tags: Vec<_> = tags.into_iter().map(|tag| tag.as_ref().to_string()).collect();
+        let is_valid = |tag: &&String| !tag.is_empty() && tag.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+        if let Some(tag) = tags.iter().find(|tag| !is_valid(tag)) {
+            return Err(Error::InvalidLanguageTag(tag.to_string()));
+        }
+        let lang = if tags.is_empty() { None } else { Some(tags.join(",")) };
+        self.with_param(LANG, lang.as_deref())
+    }
+
+    /// Sets the `charset` parameter.
+    ///
+    /// Fails if `charset` is not a valid MIME parameter value without quoting.
+    pub fn with_charset(self, charset: &str) -> Result<Self, Error> {
+        if !is_token(charset) {
+            return Err(Error::InvalidCharset(charset.to_string()));
+        }
+        self.with_param(CHARSET.as_str(), Some(charset))
+    }
+
+    fn with_param(self, name: &str, value: Option<&str>) -> Result<Self, Error> {
+        let mut mime = self.essence().to_string();
+        for (param, param_value) in self.0.params().filter(|(param, _)| *param != name) {
+            push_param(&mut mime, param.as_str(), param_value.as_str(), true);
+        }
+        if let Some(value) = value {
+            push_param(&mut mime, name, value, true);
+        }
+        mime.parse().map(Self).map_err(|_| Error::InvalidMime(mime))
+    }
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?=".contains(&b))
+}
+
+/// Appends `; name=value`, quoting the value if it is not a token. Commas are left unquoted
+/// unless `quote_commas` is set.
+fn push_param(out: &mut String, name: &str, value: &str, quote_commas: bool) {
+    out.push_str("; ");
+    out.push_str(name);
+    out.push('=');
+    let needs_quotes = if quote_commas {
+        !is_token(value)
+    } else {
+        !value.split(',').all(is_token)
+    };
+    if needs_quotes {
+        out.push('"');
+        for c in value.chars() {
+            if c == '"' || c == '\\' {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        out.push('"');
+    } else {
+        out.push_str(value);
+    }
+}
+
+impl From<Mime> for GeminiMime {
+    fn from(mime: Mime) -> Self {
+        Self(mime)
+    }
+}
+
+impl From<GeminiMime> for Mime {
+    fn from(mime: GeminiMime) -> Self {
+        mime.0
+    }
+}
+
+impl AsRef<Mime> for GeminiMime {
+    fn as_ref(&self) -> &Mime {
+        &self.0
+    }
+}
+
+impl fmt::Display for GeminiMime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut mime = self.essence().to_string();
+        for (name, value) in self.0.params() {
+            push_param(&mut mime, name.as_str(), value.as_str(), false);
+        }
+        f.write_str(&mime)
+    }
+}
+
+impl FromStr for GeminiMime {
+    type Err = FromStrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = match s.parse() {
+            Ok(mime) => return Ok(Self(mime)),
+            Err(error) => error,
+        };
+
+        // Retry with unquoted values that contain commas quoted.
+        let mut parts = s.split(';');
+        let mut quoted = parts.next().unwrap_or_default().to_string();
+        for part in parts {
+            match part.split_once('=') {
+                Some((name, value)) if !value.trim().starts_with('"') && value.contains(',') => {
+                    push_param(&mut quoted, name.trim(), value.trim(), true);
+                }
+                _ => {
+                    quoted.push(';');
+                    quoted.push_str(part);
+                }
+            }
+        }
+        quoted.parse().map(Self).map_err(|_| error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mime::IMAGE_PNG;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    #[test_case("text/gemini", &[] ; "no lang")]
+    #[test_case("text/gemini; lang=en", &["en"] ; "one tag")]
+    #[test_case("text/gemini; lang=en,fr-CA", &["en", "fr-CA"] ; "unquoted list")]
+    #[test_case("text/gemini; charset=utf-8; lang=\"en, fr\"", &["en", "fr"] ; "quoted list")]
+    fn test_lang(mime: &str, expected: &[&str]) {
+        let mime: GeminiMime = mime.parse().unwrap();
+        assert_eq!(mime.lang(), expected);
+    }
+
+    #[test_case("text/gemini", "utf-8" ; "default")]
+    #[test_case("text/plain; charset=iso-8859-1", "iso-8859-1" ; "explicit")]
+    fn test_charset(mime: &str, expected: &str) {
+        let mime: GeminiMime = mime.parse().unwrap();
+        assert_eq!(mime.charset(), expected);
+    }
+
+    #[test]
+    fn test_essence() {
+        let mime: GeminiMime = "text/gemini; lang=en".parse().unwrap();
+        assert_eq!(mime.essence(), "text/gemini");
+        assert!(mime.is_gemtext());
+        assert!(mime.is_text());
+
+        let mime = GeminiMime::from(IMAGE_PNG);
+        assert!(!mime.is_gemtext());
+        assert!(!mime.is_text());
+    }
+
+    #[test_case(GeminiMime::gemtext(), "text/gemini" ; "gemtext")]
+    #[test_case(GeminiMime::gemtext().with_lang(["en", "fr"]).unwrap(), "text/gemini; lang=en,fr" ; "with lang")]
+    #[test_case(
+        GeminiMime::gemtext().with_lang(["en"]).unwrap().with_charset("utf-8").unwrap().with_lang(["de"]).unwrap(),
+        "text/gemini; charset=utf-8; lang=de" ; "lang replaced"
+    )]
+    #[test_case(
+        GeminiMime::gemtext().with_lang(["en"]).unwrap().with_lang::<[&str; 0]>([]).unwrap(),
+        "text/gemini" ; "lang removed"
+    )]
+    #[test_case("text/plain;charset=\"us-ascii\"".parse().unwrap(), "text/plain; charset=us-ascii" ; "unneeded quotes")]
+    fn test_display(mime: GeminiMime, expected: &str) {
+        assert_eq!(mime.to_string(), expected);
+        assert_eq!(expected.parse::<GeminiMime>().unwrap(), mime);
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        assert_eq!(
+            GeminiMime::gemtext().with_lang(["en; charset=latin1"]),
+            Err(Error::InvalidLanguageTag("en; charset=latin1".to_string()))
+        );
+        assert_eq!(GeminiMime::gemtext().with_lang(["en", ""]), Err(Error::InvalidLanguageTag(String::new())));
+        assert_eq!(GeminiMime::gemtext().with_charset("utf 8"), Err(Error::InvalidCharset("utf 8".to_string())));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!("text".parse::<GeminiMime>().is_err());
+        assert!("text/gemini; lang".parse::<GeminiMime>().is_err());
+    }
+}
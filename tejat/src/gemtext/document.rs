@@ -0,0 +1,267 @@
+use std::borrow::Cow;
+
+use super::{Heading, Link, Preformatted, RawLine};
+
+/// A group of consecutive lines of the same kind.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Block<'s> {
+    /// Consecutive non-empty text lines.
+    Paragraph(Vec<Cow<'s, str>>),
+    /// The number of consecutive empty text lines.
+    Blank(usize),
+    Heading(Heading<'s>),
+    LinkGroup(Vec<Link<'s>>),
+    List(Vec<Cow<'s, str>>),
+    Quote(Vec<Cow<'s, str>>),
+    Preformatted(Preformatted<'s>),
+}
+
+impl<'s> Block<'s> {
+    /// Adds `line` to this block if it belongs to it, otherwise returns it.
+    fn try_extend(&mut self, line: RawLine<'s>) -> Option<RawLine<'s>> {
+        match (self, line) {
+            (Self::Paragraph(lines), RawLine::Text(text)) if !text.is_empty() => lines.push(text),
+            (Self::Blank(count), RawLine::Text(text)) if text.is_empty() => *count += 1,
+            (Self::LinkGroup(links), RawLine::Link(link)) => links.push(link),
+            (Self::List(items), RawLine::ListItem(item)) => items.push(item),
+            (Self::Quote(lines), RawLine::Blockquote(text)) => lines.push(text),
+            (_, line) => return Some(line),
+        }
+        None
+    }
+
+    pub fn into_lines(self) -> Vec<RawLine<'s>> {
+        match self {
+            Self::Paragraph(lines) => lines.into_iter().map(RawLine::Text).collect(),
+            Self::Blank(count) => vec![RawLine::Text(Cow::Borrowed("")); count],
+            Self::Heading(heading) => vec![RawLine::Heading(heading)],
+            Self::LinkGroup(links) => links.into_iter().map(RawLine::Link).collect(),
+            Self::List(items) => items.into_iter().map(RawLine::ListItem).collect(),
+            Self::Quote(lines) => lines.into_iter().map(RawLine::Blockquote).collect(),
+            Self::Preformatted(pre) => vec![RawLine::Preformatted(pre)],
+        }
+    }
+
+    pub fn into_static(self) -> Block<'static> {
+        fn owned(lines: Vec<Cow<'_, str>>) -> Vec<Cow<'static, str>> {
+            lines.into_iter().map(|s| Cow::Owned(s.into_owned())).collect()
+        }
+
+        match self {
+            Self::Paragraph(lines) => Block::Paragraph(owned(lines)),
+            Self::Blank(count) => Block::Blank(count),
+            Self::Heading(heading) => Block::Heading(heading.into_static()),
+            Self::LinkGroup(links) => Block::LinkGroup(links.into_iter().map(Link::into_static).collect()),
+            Self::List(items) => Block::List(owned(items)),
+            Self::Quote(lines) => Block::Quote(owned(lines)),
+            Self::Preformatted(pre) => Block::Preformatted(pre.into_static()),
+        }
+    }
+}
+
+impl<'s> From<RawLine<'s>> for Block<'s> {
+    fn from(line: RawLine<'s>) -> Self {
+        match line {
+            RawLine::Text(text) if text.is_empty() => Self::Blank(1),
+            RawLine::Text(text) => Self::Paragraph(vec![text]),
+            RawLine::Heading(heading) => Self::Heading(heading),
+            RawLine::Link(link) => Self::LinkGroup(vec![link]),
+            RawLine::ListItem(item) => Self::List(vec![item]),
+            RawLine::Blockquote(text) => Self::Quote(vec![text]),
+            RawLine::Preformatted(pre) => Self::Preformatted(pre),
+        }
+    }
+}
+
+/// A gemtext document, with its lines grouped into [`Block`]s.
+///
+/// Converting a list of lines into a `Document` and back is lossless.
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Document<'s> {
+    blocks: Vec<Block<'s>>,
+}
+
+impl<'s> Document<'s> {
+    pub fn blocks(&self) -> &[Block<'s>] {
+        &self.blocks
+    }
+
+    pub fn into_blocks(self) -> Vec<Block<'s>> {
+        self.blocks
+    }
+
+    pub fn into_lines(self) -> Vec<RawLine<'s>> {
+        self.blocks.into_iter().flat_map(Block::into_lines).collect()
+    }
+
+    pub fn into_static(self) -> Document<'static> {
+        Document {
+            blocks: self.blocks.into_iter().map(Block::into_static).collect(),
+        }
+    }
+
+    /// Returns the document's outline.
+    ///
+    /// The returned section has no heading and contains the blocks before the first heading.
+    /// Each heading starts a section that contains every following block up to the next
+    /// heading of the same or a higher level. Skipped levels are allowed: a `###` directly
+    /// after a `#` is a subsection of it.
+    pub fn sections(&self) -> Section<'_, 's> {
+        let preamble_len = self.blocks.iter()
+            .position(|block| matches!(block, Block::Heading(_)))
+            .unwrap_or(self.blocks.len());
+        let mut index = preamble_len;
+        Section {
+            heading: None,
+            blocks: &self.blocks[..preamble_len],
+            subsections: collect_sections(&self.blocks, &mut index, 0),
+        }
+    }
+}
+
+impl<'s> FromIterator<RawLine<'s>> for Document<'s> {
+    fn from_iter<I: IntoIterator<Item = RawLine<'s>>>(lines: I) -> Self {
+        let mut blocks: Vec<Block<'s>> = Vec::new();
+        for line in lines {
+            let line = match blocks.last_mut() {
+                Some(block) => block.try_extend(line),
+                None => Some(line),
+            };
+            blocks.extend(line.map(Block::from));
+        }
+        Self { blocks }
+    }
+}
+
+impl<'s> From<Vec<RawLine<'s>>> for Document<'s> {
+    fn from(lines: Vec<RawLine<'s>>) -> Self {
+        lines.into_iter().collect()
+    }
+}
+
+impl<'s> From<Document<'s>> for Vec<RawLine<'s>> {
+    fn from(document: Document<'s>) -> Self {
+        document.into_lines()
+    }
+}
+
+/// A part of a [`Document`] introduced by a heading. See [`Document::sections`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Section<'d, 's> {
+    pub heading: Option<&'d Heading<'s>>,
+    /// The blocks between the heading and the first subsection, if any.
+    pub blocks: &'d [Block<'s>],
+    pub subsections: Vec<Section<'d, 's>>,
+}
+
+fn collect_sections<'d, 's>(blocks: &'d [Block<'s>], index: &mut usize, parent_level: u8) -> Vec<Section<'d, 's>> {
+    let mut sections = Vec::new();
+    while let Some(Block::Heading(heading)) = blocks.get(*index) {
+        if heading.level() <= parent_level {
+            break;
+        }
+
+        *index += 1;
+        let start = *index;
+        while blocks.get(*index).is_some_and(|block| !matches!(block, Block::Heading(_))) {
+            *index += 1;
+        }
+
+        sections.push(Section {
+            heading: Some(heading),
+            blocks: &blocks[start..*index],
+            subsections: collect_sections(blocks, index, heading.level()),
+        });
+    }
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemtext::parse_lines;
+    use pretty_assertions::assert_eq;
+
+    const DOCUMENT: &str = "\
+intro
+more intro
+
+# One
+=> /a A
+=> /b B
+* item 1
+* item 2
+> quote
+> quote
+### One, Three
+```
+code
+```
+## One, Two
+text
+# Two
+";
+
+    fn borrowed(lines: &[&'static str]) -> Vec<Cow<'static, str>> {
+        lines.iter().copied().map(Cow::Borrowed).collect()
+    }
+
+    #[test]
+    fn test_groups_consecutive_lines() {
+        let document = Document::from(parse_lines(DOCUMENT));
+        assert_eq!(document.blocks(), &[
+            Block::Paragraph(borrowed(&["intro", "more intro"])),
+            Block::Blank(1),
+            Block::Heading(Heading::H1(Cow::Borrowed("One"))),
+            Block::LinkGroup(vec![
+                Link::new("/a", Some("A")).unwrap(),
+                Link::new("/b", Some("B")).unwrap(),
+            ]),
+            Block::List(borrowed(&["item 1", "item 2"])),
+            Block::Quote(borrowed(&["quote", "quote"])),
+            Block::Heading(Heading::H3(Cow::Borrowed("One, Three"))),
+            Block::Preformatted(Preformatted { alt_text: None, text: Cow::Borrowed("code") }),
+            Block::Heading(Heading::H2(Cow::Borrowed("One, Two"))),
+            Block::Paragraph(borrowed(&["text"])),
+            Block::Heading(Heading::H1(Cow::Borrowed("Two"))),
+        ]);
+    }
+
+    #[test]
+    fn test_into_lines_is_lossless() {
+        let lines = parse_lines(DOCUMENT);
+        assert_eq!(Document::from(lines.clone()).into_lines(), lines);
+    }
+
+    #[test]
+    fn test_section_tree() {
+        let document = Document::from(parse_lines(DOCUMENT));
+        let blocks = document.blocks();
+        let root = document.sections();
+
+        assert_eq!(root.heading, None);
+        assert_eq!(root.blocks, &blocks[0..2]);
+        assert_eq!(root.subsections.len(), 2);
+
+        let one = &root.subsections[0];
+        assert_eq!(one.heading.map(Heading::text), Some("One"));
+        assert_eq!(one.blocks, &blocks[3..6]);
+        let titles: Vec<_> = one.subsections.iter().map(|s| s.heading.unwrap().text()).collect();
+        assert_eq!(titles, ["One, Three", "One, Two"]);
+        assert_eq!(one.subsections[0].blocks, &blocks[7..8]);
+        assert_eq!(one.subsections[1].blocks, &blocks[9..10]);
+
+        let two = &root.subsections[1];
+        assert_eq!(two.heading.map(Heading::text), Some("Two"));
+        assert!(two.blocks.is_empty());
+        assert!(two.subsections.is_empty());
+    }
+
+    #[test]
+    fn test_empty_document() {
+        let document = Document::from(Vec::new());
+        let root = document.sections();
+        assert!(root.blocks.is_empty());
+        assert!(root.subsections.is_empty());
+    }
+}
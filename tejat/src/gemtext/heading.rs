@@ -1,16 +1,58 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt};
 
 use nom::{branch::alt, combinator::map};
 
 use super::parser::{Error, Input, line_with_leader, impl_from_str};
+#[cfg(feature = "serde")]
+use super::single_line;
 
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "HeadingRepr<'s>", try_from = "HeadingRepr<'s>"),
+)]
 pub enum Heading<'s> {
     H1(Cow<'s, str>),
     H2(Cow<'s, str>),
     H3(Cow<'s, str>),
 }
 
+/// A heading as serialized: `{"level": 1, "text": "..."}`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct HeadingRepr<'s> {
+    level: u8,
+    text: Cow<'s, str>,
+}
+
+#[cfg(feature = "serde")]
+impl<'s> From<Heading<'s>> for HeadingRepr<'s> {
+    fn from(heading: Heading<'s>) -> Self {
+        let (level, text) = match heading {
+            Heading::H1(text) => (1, text),
+            Heading::H2(text) => (2, text),
+            Heading::H3(text) => (3, text),
+        };
+        Self { level, text }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'s> TryFrom<HeadingRepr<'s>> for Heading<'s> {
+    type Error = String;
+
+    fn try_from(repr: HeadingRepr<'s>) -> Result<Self, Self::Error> {
+        let text = single_line("heading", repr.text)?;
+        match repr.level {
+            1 => Ok(Heading::H1(text)),
+            2 => Ok(Heading::H2(text)),
+            3 => Ok(Heading::H3(text)),
+            level => Err(format!("invalid heading level {}", level)),
+        }
+    }
+}
+
 impl Heading<'_> {
     pub fn into_static(self) -> Heading<'static> {
         match self {
@@ -19,10 +61,25 @@ impl Heading<'_> {
             Self::H3(s) => Heading::H3(s.into_owned().into()),
         }
     }
+
+    /// The heading level, from 1 (`#`) to 3 (`###`).
+    pub fn level(&self) -> u8 {
+        match self {
+            Self::H1(_) => 1,
+            Self::H2(_) => 2,
+            Self::H3(_) => 3,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            Self::H1(s) | Self::H2(s) | Self::H3(s) => s,
+        }
+    }
 }
 
 impl<'s> Heading<'s> {
-    pub(crate) fn parse(input: Input<'s>) -> nom::IResult<Input<'_>, Self, Error<'s>> {
+    pub(crate) fn parse(input: Input<'s>) -> nom::IResult<Input<'s>, Self, Error<'s>> {
         alt((
             map(line_with_leader("###"), Heading::H3),
             map(line_with_leader("##"), Heading::H2),
@@ -31,6 +88,16 @@ impl<'s> Heading<'s> {
     }
 }
 
+impl fmt::Display for Heading<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::H1(text) => write!(f, "# {}", text),
+            Self::H2(text) => write!(f, "## {}", text),
+            Self::H3(text) => write!(f, "### {}", text),
+        }
+    }
+}
+
 impl_from_str!(Heading);
 
 #[cfg(test)]
@@ -43,4 +110,12 @@ mod tests {
         let value: Heading = input.parse().expect("should parse successfully");
         assert_eq!(value, expected);
     }
+
+    #[test_case(Heading::H1(Cow::Borrowed("One")), "# One")]
+    #[test_case(Heading::H2(Cow::Borrowed("Two")), "## Two")]
+    #[test_case(Heading::H3(Cow::Borrowed("#Three")), "### #Three")]
+    fn test_display(heading: Heading<'static>, expected: &str) {
+        assert_eq!(heading.to_string(), expected);
+        assert_eq!(expected.parse::<Heading>().expect("should parse successfully"), heading);
+    }
 }
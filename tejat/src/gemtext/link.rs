@@ -1,21 +1,55 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt};
 
-use nom::{combinator::{recognize, not, opt, map, peek, eof}, multi::{many1_count, many_till}, character::complete::{space1, space0, anychar}, sequence::{tuple, preceded}, branch::alt};
+use nom::{combinator::map, bytes::complete::take_till, character::complete::{space1, space0}, sequence::{tuple, preceded, pair}, branch::alt};
 use url::Url;
 
-use super::{parser::{Input, IResult, better_tag, optional_str_until_newline, impl_from_str}, Error};
+use crate::uri::GeminiUrl;
+use super::{parser::{Input, IResult, better_tag, line_end, optional_str_until_newline, impl_from_str}, Error};
+#[cfg(feature = "serde")]
+use super::single_line;
 
+/// The kind of URL scheme a link points to.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Scheme {
+    Gemini,
+    /// `http` or `https`.
+    Http,
+    Gopher,
+    Mailto,
+    Titan,
+    Spartan,
+    Other,
+}
+
+impl From<&str> for Scheme {
+    fn from(scheme: &str) -> Self {
+        match scheme.to_ascii_lowercase().as_str() {
+            "gemini" => Self::Gemini,
+            "http" | "https" => Self::Http,
+            "gopher" => Self::Gopher,
+            "mailto" => Self::Mailto,
+            "titan" => Self::Titan,
+            "spartan" => Self::Spartan,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Where a link points to.
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
-enum LinkTarget<'s> {
+pub enum LinkTarget<'s> {
     Absolute(Url),
+    /// A relative reference, or anything else that does not parse as an absolute URL.
     Relative(Cow<'s, str>),
 }
 
 impl<'s> LinkTarget<'s> {
-    pub(crate) fn parse(input: Input<'s>) -> IResult<Self> {
+    pub(crate) fn parse(input: Input<'s>) -> IResult<'s, Self> {
         map(
-            recognize(many_till(anychar, peek(alt((space1, eof))))),
-            |s: Input<'_>| { 
+            // Only ASCII spaces and tabs separate the target from the text, so other whitespace,
+            // like a no-break space, is part of the target.
+            take_till(|c| matches!(c, ' ' | '\t' | '\r' | '\n')),
+            |s: Input<'s>| {
                 s.parse::<Url>()
                     .map(LinkTarget::Absolute)
                     .unwrap_or_else(|_| LinkTarget::Relative(Cow::Borrowed(*s.fragment())))
@@ -23,20 +57,94 @@ impl<'s> LinkTarget<'s> {
         )(input)
     }
 
-    fn into_static(self) -> LinkTarget<'static> {
+    pub fn into_static(self) -> LinkTarget<'static> {
         match self {
             Self::Absolute(url) => LinkTarget::Absolute(url),
             Self::Relative(path) => LinkTarget::Relative(Cow::Owned(path.into_owned())),
         }
     }
+
+    /// The target as it appears in a link line.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Absolute(url) => url.as_str(),
+            Self::Relative(path) => path,
+        }
+    }
+
+    pub fn is_relative(&self) -> bool {
+        matches!(self, Self::Relative(_))
+    }
+
+    /// The scheme of an absolute target. Relative targets use the scheme of the document they
+    /// are in, so they have none.
+    pub fn scheme(&self) -> Option<Scheme> {
+        match self {
+            Self::Absolute(url) => Some(Scheme::from(url.scheme())),
+            Self::Relative(_) => None,
+        }
+    }
+
+    /// Resolves the target against `base` as described in
+    /// [RFC 3986, section 5](https://www.rfc-editor.org/rfc/rfc3986#section-5).
+    ///
+    /// Absolute targets are returned as they are. This fails if a relative target is not a
+    /// valid relative reference, e.g. if it contains an invalid host.
+    pub fn resolve(&self, base: &Url) -> Result<Url, url::ParseError> {
+        match self {
+            Self::Absolute(url) => Ok(url.clone()),
+            Self::Relative(path) => base.join(path),
+        }
+    }
+}
+
+impl fmt::Display for LinkTarget<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "LinkRepr<'s>", try_from = "LinkRepr<'s>"),
+)]
 pub struct Link<'s> {
     target: LinkTarget<'s>,
     text: Option<Cow<'s, str>>,
 }
 
+/// A link as serialized: `{"url": "...", "text": "..." | null}`, with the target as it appears
+/// in the link line.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct LinkRepr<'s> {
+    url: Cow<'s, str>,
+    text: Option<Cow<'s, str>>,
+}
+
+#[cfg(feature = "serde")]
+impl<'s> From<Link<'s>> for LinkRepr<'s> {
+    fn from(link: Link<'s>) -> Self {
+        let url = match link.target {
+            LinkTarget::Absolute(url) => Cow::Owned(url.into()),
+            LinkTarget::Relative(path) => path,
+        };
+        Self { url, text: link.text }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'s> TryFrom<LinkRepr<'s>> for Link<'s> {
+    type Error = String;
+
+    fn try_from(repr: LinkRepr<'s>) -> Result<Self, Self::Error> {
+        let text = repr.text.map(|text| single_line("link text", text)).transpose()?;
+        Link::new(repr.url, text).map_err(|error| error.to_string())
+    }
+}
+
 impl<'s> Link<'s> {
     pub fn new<S>(url: S, text: Option<S>) -> Result<Self, Error<'s>>
         where S: Into<Cow<'s, str>>,
@@ -50,7 +158,21 @@ impl<'s> Link<'s> {
         })
     }
 
-    pub(crate) fn parse(input: Input<'s>) -> IResult<Self> {
+    pub fn target(&self) -> &LinkTarget<'s> {
+        &self.target
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    /// Resolves the link's target against the URL of the document it is in.
+    /// See [`LinkTarget::resolve`].
+    pub fn resolve(&self, base: &GeminiUrl) -> Result<Url, url::ParseError> {
+        self.target.resolve(base.as_url())
+    }
+
+    pub(crate) fn parse(input: Input<'s>) -> IResult<'s, Self> {
         map(
             tuple((
                 better_tag("=>"),
@@ -58,7 +180,7 @@ impl<'s> Link<'s> {
                 LinkTarget::parse,
                 alt((
                     preceded(space1, optional_str_until_newline),
-                    map(space0, |_| None),
+                    map(pair(space0, line_end), |_| None),
                 )),
             )),
             |(_, _, target, text)| {
@@ -73,6 +195,28 @@ impl<'s> Link<'s> {
             text: self.text.map(|s| Cow::Owned(s.into_owned())),
         }
     }
+
+    /// Applies `f` to the link's text and, if the target is relative, to the target.
+    pub(crate) fn map_strs(self, mut f: impl FnMut(Cow<'s, str>) -> Cow<'s, str>) -> Self {
+        let target = match self.target {
+            LinkTarget::Relative(path) => LinkTarget::Relative(f(path)),
+            absolute => absolute,
+        };
+        Link {
+            target,
+            text: self.text.map(f),
+        }
+    }
+}
+
+impl fmt::Display for Link<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "=> {}", self.target)?;
+        if let Some(text) = &self.text {
+            write!(f, " {}", text)?;
+        }
+        Ok(())
+    }
 }
 
 impl_from_str!(Link);
@@ -90,8 +234,115 @@ mod tests {
             text: None,
         }
     )]
+    #[test_case(
+        "=> /a\u{a0}b text",
+        Link {
+            target: LinkTarget::Relative(Cow::Borrowed("/a\u{a0}b")),
+            text: Some(Cow::Borrowed("text")),
+        } ;
+        "no-break space in target"
+    )]
+    #[test_case(
+        "=> gemini://x.org/\u{3000} t",
+        Link {
+            target: LinkTarget::Absolute(Url::parse("gemini://x.org/\u{3000}").unwrap()),
+            text: Some(Cow::Borrowed("t")),
+        } ;
+        "ideographic space in target"
+    )]
     fn test_from_str_valid(input: &str, expected: Link<'static>) {
         let link: Link = input.parse().expect("should be a valid link line");
         assert_eq!(link, expected);
     }
+
+    #[test_case(
+        Link::new("gemini://example.com", Some("Example")).unwrap().into_static(),
+        "=> gemini://example.com Example"
+    )]
+    #[test_case(
+        Link::new("relative/url", None).unwrap().into_static(),
+        "=> relative/url"
+    )]
+    fn test_display(link: Link<'static>, expected: &str) {
+        assert_eq!(link.to_string(), expected);
+        assert_eq!(expected.parse::<Link>().expect("should be a valid link line"), link);
+    }
+
+    #[test_case("gemini://example.com/", Some(Scheme::Gemini))]
+    #[test_case("HTTP://example.com/", Some(Scheme::Http))]
+    #[test_case("https://example.com/", Some(Scheme::Http))]
+    #[test_case("gopher://example.com/", Some(Scheme::Gopher))]
+    #[test_case("mailto:someone@example.com", Some(Scheme::Mailto))]
+    #[test_case("titan://example.com/upload", Some(Scheme::Titan))]
+    #[test_case("spartan://example.com/", Some(Scheme::Spartan))]
+    #[test_case("ftp://example.com/", Some(Scheme::Other))]
+    #[test_case("/relative", None)]
+    fn test_scheme(target: &str, expected: Option<Scheme>) {
+        let target: LinkTarget = target.parse().unwrap();
+        assert_eq!(target.scheme(), expected);
+        assert_eq!(target.is_relative(), expected.is_none());
+    }
+
+    #[test]
+    fn test_resolve() {
+        // The examples from RFC 3986, section 5.4, with a gemini base URL.
+        let examples = [
+            ("g:h", "g:h"),
+            ("g", "gemini://a/b/c/g"),
+            ("./g", "gemini://a/b/c/g"),
+            ("g/", "gemini://a/b/c/g/"),
+            ("/g", "gemini://a/g"),
+            ("//g", "gemini://g"),
+            ("?y", "gemini://a/b/c/d;p?y"),
+            ("g?y", "gemini://a/b/c/g?y"),
+            ("#s", "gemini://a/b/c/d;p?q#s"),
+            ("g#s", "gemini://a/b/c/g#s"),
+            ("g?y#s", "gemini://a/b/c/g?y#s"),
+            (";x", "gemini://a/b/c/;x"),
+            ("g;x", "gemini://a/b/c/g;x"),
+            ("g;x?y#s", "gemini://a/b/c/g;x?y#s"),
+            ("", "gemini://a/b/c/d;p?q"),
+            (".", "gemini://a/b/c/"),
+            ("./", "gemini://a/b/c/"),
+            ("..", "gemini://a/b/"),
+            ("../", "gemini://a/b/"),
+            ("../g", "gemini://a/b/g"),
+            ("../..", "gemini://a/"),
+            ("../../", "gemini://a/"),
+            ("../../g", "gemini://a/g"),
+            ("../../../g", "gemini://a/g"),
+            ("../../../../g", "gemini://a/g"),
+            ("/./g", "gemini://a/g"),
+            ("/../g", "gemini://a/g"),
+            ("g.", "gemini://a/b/c/g."),
+            (".g", "gemini://a/b/c/.g"),
+            ("g..", "gemini://a/b/c/g.."),
+            ("..g", "gemini://a/b/c/..g"),
+            ("./../g", "gemini://a/b/g"),
+            ("./g/.", "gemini://a/b/c/g/"),
+            ("g/./h", "gemini://a/b/c/g/h"),
+            ("g/../h", "gemini://a/b/c/h"),
+            ("g;x=1/./y", "gemini://a/b/c/g;x=1/y"),
+            ("g;x=1/../y", "gemini://a/b/c/y"),
+        ];
+        let base = Url::parse("gemini://a/b/c/d;p?q").unwrap();
+        for (target, expected) in examples {
+            let resolved = target.parse::<LinkTarget>().unwrap().resolve(&base).unwrap();
+            assert_eq!(resolved.as_str(), expected, "resolving {:?}", target);
+        }
+    }
+
+    #[test]
+    fn test_resolve_link() {
+        let base: GeminiUrl = "gemini://example.com/dir/page.gmi".parse().unwrap();
+        let link: Link = "=> ../other.gmi Other".parse().unwrap();
+        assert_eq!(link.resolve(&base).unwrap().as_str(), "gemini://example.com/other.gmi");
+    }
+
+    #[test]
+    fn test_resolve_invalid_target() {
+        let base = Url::parse("gemini://example.com/").unwrap();
+        let target: LinkTarget = "//[::1".parse().unwrap();
+        assert!(target.resolve(&base).is_err());
+    }
 }
@@ -74,12 +74,12 @@ macro_rules! preformatted {
         ::std::borrow::Cow::Borrowed($base)
     };
     (@fmt $base: literal, $($arg: tt),+) => {
-        ::std::borrow::Cow::Owned(::std::format!($url, $($arg),+))
+        ::std::borrow::Cow::Owned(::std::format!($base, $($arg),+))
     };
     ($text: literal $(, $text_arg: tt)*) => {
         $crate::preformatted!(@common
             None,
-            $crate::preformatted!(@fmt $url $(, $url_arg)*)
+            $crate::preformatted!(@fmt $text $(, $text_arg)*)
         )
     };
     ($alt: literal $(, $alt_arg: tt)*: $text: literal $(, $text_arg: tt)*) => {
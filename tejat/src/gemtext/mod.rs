@@ -1,21 +1,54 @@
+mod document;
 mod heading;
 mod link;
 mod macros;
 mod parser;
+mod span;
+mod stream;
 
-use parser::{impl_from_str, Input, IResult};
+use parser::{Input, IResult};
 pub use parser::{Error, ErrorKind};
 
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt, io};
 
-use nom::{Finish, sequence::{tuple, pair}, bytes::complete::tag, combinator::{recognize, not, opt, map, map_res}, multi::{many1_count, many_till}, character::complete::{line_ending, space0, space1, anychar}, branch::alt};
+use nom::{Finish, sequence::preceded, combinator::{recognize, not, map, eof, opt}, multi::many0, branch::alt};
 
+pub use document::{Block, Document, Section};
 pub use heading::Heading;
-pub use link::Link;
+pub use link::{Link, LinkTarget, Scheme};
+pub use span::{Span, Spanned};
+pub use stream::StreamParser;
+#[cfg(feature = "tokio")]
+pub use stream::AsyncStreamParser;
 
-use self::parser::{line_with_leader, str_until_newline, line_end, better_tag, optional_str_until_newline, repeated_all_consuming};
+use self::parser::{line_with_leader, str_until_newline, better_tag, optional_str_until_newline};
 
+const PREFORMATTED_TOGGLE: &str = "```";
+
+/// A single gemtext line, or a whole preformatted block.
+///
+/// # Serialization
+///
+/// With the `serde` feature, lines are serialized as objects tagged with their `type`:
+///
+/// | Line               | JSON                                                                    |
+/// |--------------------|-------------------------------------------------------------------------|
+/// | `Text`             | `{"type": "text", "text": "..."}`                                       |
+/// | `Heading`          | `{"type": "heading", "level": 1, "text": "..."}`                        |
+/// | `Link`             | `{"type": "link", "url": "...", "text": "..." \| null}`                 |
+/// | `ListItem`         | `{"type": "list_item", "text": "..."}`                                  |
+/// | `Blockquote`       | `{"type": "blockquote", "text": "..."}`                                 |
+/// | `Preformatted`     | `{"type": "preformatted", "alt_text": "..." \| null, "text": "..."}`    |
+///
+/// [`Heading`], [`Link`] and [`Preformatted`] on their own are serialized as the same objects,
+/// without the `type`. Deserializing fails for text that would not stay a single line, like a
+/// heading text containing a line break or a preformatted line starting with ```` ``` ````.
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "RawLineRepr<'s>", try_from = "RawLineRepr<'s>"),
+)]
 pub enum RawLine<'s> {
     Blockquote(Cow<'s, str>),
     Heading(Heading<'s>),
@@ -25,50 +58,436 @@ pub enum RawLine<'s> {
     Text(Cow<'s, str>),
 }
 
+impl RawLine<'_> {
+    pub fn into_static(self) -> RawLine<'static> {
+        match self {
+            Self::Blockquote(s) => RawLine::Blockquote(Cow::Owned(s.into_owned())),
+            Self::Heading(heading) => RawLine::Heading(heading.into_static()),
+            Self::Link(link) => RawLine::Link(link.into_static()),
+            Self::ListItem(s) => RawLine::ListItem(Cow::Owned(s.into_owned())),
+            Self::Preformatted(pre) => RawLine::Preformatted(pre.into_static()),
+            Self::Text(s) => RawLine::Text(Cow::Owned(s.into_owned())),
+        }
+    }
+}
+
+/// Writes the line as canonical gemtext, without a trailing line ending.
+///
+/// A [`RawLine::Preformatted`] spans multiple lines, including both toggle lines. Gemtext has no
+/// escapes, so a [`RawLine::Text`] that would be read as another line type, like `# not a
+/// heading`, is written with a leading space to keep it a text line.
+impl fmt::Display for RawLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Blockquote(text) => write!(f, "> {}", text),
+            Self::Heading(heading) => write!(f, "{}", heading),
+            Self::Link(link) => write!(f, "{}", link),
+            Self::ListItem(text) => write!(f, "* {}", text),
+            Self::Preformatted(pre) => write!(f, "{}", pre),
+            Self::Text(text) if looks_like_other_line(text) => write!(f, " {}", text),
+            Self::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RawLineRepr<'s> {
+    Text { text: Cow<'s, str> },
+    Heading(Heading<'s>),
+    Link(Link<'s>),
+    ListItem { text: Cow<'s, str> },
+    Blockquote { text: Cow<'s, str> },
+    Preformatted(Preformatted<'s>),
+}
+
+#[cfg(feature = "serde")]
+impl<'s> From<RawLine<'s>> for RawLineRepr<'s> {
+    fn from(line: RawLine<'s>) -> Self {
+        match line {
+            RawLine::Text(text) => Self::Text { text },
+            RawLine::Heading(heading) => Self::Heading(heading),
+            RawLine::Link(link) => Self::Link(link),
+            RawLine::ListItem(text) => Self::ListItem { text },
+            RawLine::Blockquote(text) => Self::Blockquote { text },
+            RawLine::Preformatted(pre) => Self::Preformatted(pre),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'s> TryFrom<RawLineRepr<'s>> for RawLine<'s> {
+    type Error = String;
+
+    fn try_from(repr: RawLineRepr<'s>) -> Result<Self, Self::Error> {
+        Ok(match repr {
+            RawLineRepr::Text { text } => Self::Text(single_line("text", text)?),
+            RawLineRepr::Heading(heading) => Self::Heading(heading),
+            RawLineRepr::Link(link) => Self::Link(link),
+            RawLineRepr::ListItem { text } => Self::ListItem(single_line("list item", text)?),
+            RawLineRepr::Blockquote { text } => Self::Blockquote(single_line("blockquote", text)?),
+            RawLineRepr::Preformatted(pre) => Self::Preformatted(pre),
+        })
+    }
+}
+
+/// Checks that deserialized `text` has no line breaks, which would turn it into several lines.
+#[cfg(feature = "serde")]
+fn single_line<'s>(what: &str, text: Cow<'s, str>) -> Result<Cow<'s, str>, String> {
+    if text.contains(['\r', '\n']) {
+        Err(format!("{} contains a line break: {:?}", what, text))
+    } else {
+        Ok(text)
+    }
+}
+
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "PreformattedRepr<'s>", try_from = "PreformattedRepr<'s>"),
+)]
 pub struct Preformatted<'s> {
     pub alt_text: Option<Cow<'s, str>>,
     pub text: Cow<'s, str>,
 }
 
-pub fn parse_lines(input: &str) -> Result<Vec<RawLine>, Error<'_>> {
-    let input = Input::new(input);
-    repeated_all_consuming(alt((
-        parse_preformatted,
+/// A preformatted block as serialized: `{"alt_text": "..." | null, "text": "..."}`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PreformattedRepr<'s> {
+    alt_text: Option<Cow<'s, str>>,
+    text: Cow<'s, str>,
+}
+
+#[cfg(feature = "serde")]
+impl<'s> From<Preformatted<'s>> for PreformattedRepr<'s> {
+    fn from(pre: Preformatted<'s>) -> Self {
+        Self { alt_text: pre.alt_text, text: pre.text }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'s> TryFrom<PreformattedRepr<'s>> for Preformatted<'s> {
+    type Error = String;
+
+    fn try_from(repr: PreformattedRepr<'s>) -> Result<Self, Self::Error> {
+        // A toggle line would end the block early.
+        if repr.text.lines().any(|line| line.starts_with(PREFORMATTED_TOGGLE)) {
+            return Err(format!("preformatted text contains a toggle line: {:?}", repr.text));
+        }
+        Ok(Self {
+            alt_text: repr.alt_text.map(|alt_text| single_line("alt text", alt_text)).transpose()?,
+            text: repr.text,
+        })
+    }
+}
+
+impl Preformatted<'_> {
+    pub fn into_static(self) -> Preformatted<'static> {
+        Preformatted {
+            alt_text: self.alt_text.map(|s| Cow::Owned(s.into_owned())),
+            text: Cow::Owned(self.text.into_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Preformatted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alt_text = self.alt_text.as_deref().unwrap_or_default();
+        write!(f, "{}{}{}", PREFORMATTED_TOGGLE, alt_text, line_ending(alt_text))?;
+        // An empty body is written as no lines at all, which is how it is parsed.
+        if !self.text.is_empty() {
+            write!(f, "{}{}", self.text, line_ending(&self.text))?;
+        }
+        f.write_str(PREFORMATTED_TOGGLE)
+    }
+}
+
+/// Something unusual, but not invalid, found while parsing a document.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Warning {
+    /// The 1-based line number the warning applies to.
+    pub line: u32,
+    pub kind: WarningKind,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "warning at line {}: {}", self.line, self.kind)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WarningKind {
+    /// A preformatted block was still open at the end of the document.
+    UnclosedPreformatted,
+    /// A closing preformatted toggle line had text after the toggle, which is ignored.
+    TextAfterClosingToggle(String),
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnclosedPreformatted => write!(f, "preformatted block is never closed"),
+            Self::TextAfterClosingToggle(text) => write!(f, "ignored text after closing toggle: {:?}", text),
+        }
+    }
+}
+
+/// Parses a gemtext document into its lines.
+///
+/// Parsing never fails: any line that is not another line type is text, and a preformatted
+/// block that is never closed runs to the end of the document. Use
+/// [`parse_lines_with_warnings`] to find out about such oddities.
+pub fn parse_lines(input: &str) -> Vec<RawLine<'_>> {
+    parse_lines_with_warnings(input).0
+}
+
+/// Like [`parse_lines`], but also returns any [`Warning`]s found while parsing.
+pub fn parse_lines_with_warnings(input: &str) -> (Vec<RawLine<'_>>, Vec<Warning>) {
+    parse_document(input, |line, _, _| line)
+}
+
+/// Like [`parse_lines_with_warnings`], but pairs each line with its location in `input`.
+pub fn parse_lines_with_spans(input: &str) -> (Vec<Spanned<RawLine<'_>>>, Vec<Warning>) {
+    parse_document(input, |value, start, rest| Spanned {
+        value,
+        span: Span::between(input, start, rest),
+    })
+}
+
+/// Parses every line in `input`, passing each one to `wrap` along with the input starting at
+/// that line and the input following it.
+fn parse_document<'s, T, F>(input: &'s str, mut wrap: F) -> (Vec<T>, Vec<Warning>)
+    where F: FnMut(RawLine<'s>, Input<'s>, Input<'s>) -> T,
+{
+    let mut input = Input::new(input);
+    let mut lines = Vec::new();
+    let mut warnings = Vec::new();
+
+    while !input.is_empty() {
+        let (rest, line) = match parse_preformatted(input) {
+            Ok((rest, block)) => {
+                match block.closing {
+                    None => warnings.push(Warning {
+                        line: input.location_line(),
+                        kind: WarningKind::UnclosedPreformatted,
+                    }),
+                    Some((line, Some(text))) => warnings.push(Warning {
+                        line,
+                        kind: WarningKind::TextAfterClosingToggle(text.into_owned()),
+                    }),
+                    Some((_, None)) => {}
+                }
+                (rest, RawLine::Preformatted(block.preformatted))
+            }
+            Err(_) => parse_line(input).finish().expect("any line should parse, at worst as text"),
+        };
+
+        lines.push(wrap(line, input, rest));
+        input = rest;
+    }
+
+    (lines, warnings)
+}
+
+/// Parses a single line, without its line ending, that is not part of a preformatted block.
+pub(crate) fn parse_single_line(line: &str) -> RawLine<'_> {
+    parse_line(Input::new(line))
+        .finish()
+        .map(|(_, value)| value)
+        .expect("any line should parse, at worst as text")
+}
+
+/// Checks whether `text` would be read as another line type if written as a text line.
+fn looks_like_other_line(text: &str) -> bool {
+    text.starts_with(PREFORMATTED_TOGGLE) || !matches!(parse_single_line(text), RawLine::Text(_))
+}
+
+/// The line ending to write after `line`: `\n`, or `\r\n` if the line ends with a CR, which
+/// would otherwise be read as part of the line ending.
+fn line_ending(line: &str) -> &'static str {
+    if line.ends_with('\r') { "\r\n" } else { "\n" }
+}
+
+/// Serializes `lines` as canonical gemtext, terminating every line with `\n`, or with `\r\n`
+/// if the line itself ends with a CR.
+///
+/// For any document produced by [`parse_lines`], `parse_lines(&serialize_lines(&doc))`
+/// returns an equal document. Text lines built by hand that look like another line type gain
+/// a leading space, as described for [`RawLine`]'s `Display`.
+pub fn serialize_lines(lines: &[RawLine<'_>]) -> String {
+    let mut output = String::new();
+    for line in lines {
+        let line = line.to_string();
+        output.push_str(&line);
+        output.push_str(line_ending(&line));
+    }
+    output
+}
+
+/// Like [`serialize_lines`], but writes the document to `writer`.
+pub fn write_lines<W: io::Write>(mut writer: W, lines: &[RawLine<'_>]) -> io::Result<()> {
+    for line in lines {
+        let line = line.to_string();
+        write!(writer, "{}{}", line, line_ending(&line))?;
+    }
+    Ok(())
+}
+
+fn parse_line(input: Input<'_>) -> IResult<'_, RawLine<'_>> {
+    alt((
         map(Heading::parse, RawLine::Heading),
         map(Link::parse, RawLine::Link),
         parse_list_item,
         parse_blockquote,
         parse_text,
-    )))(input)
-        .finish()
-        .map(|(_, value)| value)
+    ))(input)
 }
 
-fn parse_blockquote(input: Input<'_>) -> IResult<RawLine<'_>> {
+fn parse_blockquote(input: Input<'_>) -> IResult<'_, RawLine<'_>> {
     map(line_with_leader(">"), RawLine::Blockquote)(input)
 }
 
-fn parse_list_item(input: Input<'_>) -> IResult<RawLine<'_>> {
+fn parse_list_item(input: Input<'_>) -> IResult<'_, RawLine<'_>> {
     map(line_with_leader("* "), RawLine::ListItem)(input)
 }
 
-fn parse_preformatted(input: Input<'_>) -> IResult<RawLine<'_>> {
-    map(
-        tuple((
-            better_tag("```"),
-            optional_str_until_newline,
-            recognize(many_till(anychar, tuple((line_ending, tag("```"), line_ending)))),
-            tag("```"),
-            line_end
-        )),
-        |(_, alt_text, text, _, _)| {
-            let text = Cow::Borrowed(*text.fragment());
-            RawLine::Preformatted(Preformatted { alt_text, text })
-        }
-    )(input)
+struct PreformattedBlock<'s> {
+    preformatted: Preformatted<'s>,
+    /// The line number of the closing toggle line and any text following the toggle.
+    closing: Option<(u32, Option<Cow<'s, str>>)>,
+}
+
+fn parse_preformatted_toggle(input: Input<'_>) -> IResult<'_, Option<Cow<'_, str>>> {
+    preceded(better_tag(PREFORMATTED_TOGGLE), optional_str_until_newline)(input)
 }
 
-fn parse_text(input: Input<'_>) -> IResult<RawLine<'_>> {
+fn parse_preformatted(input: Input<'_>) -> IResult<'_, PreformattedBlock<'_>> {
+    let (input, alt_text) = parse_preformatted_toggle(input)?;
+    let (input, text) = recognize(many0(preceded(
+        not(alt((better_tag(PREFORMATTED_TOGGLE), eof))),
+        str_until_newline,
+    )))(input)?;
+    let line = input.location_line();
+    let (input, closing) = opt(parse_preformatted_toggle)(input)?;
+
+    // The body includes the line ending before the closing toggle line, if any.
+    let text = text.fragment();
+    let text = text.strip_suffix('\n').map(|s| s.strip_suffix('\r').unwrap_or(s)).unwrap_or(text);
+    Ok((input, PreformattedBlock {
+        preformatted: Preformatted { alt_text, text: Cow::Borrowed(text) },
+        closing: closing.map(|trailing| (line, trailing)),
+    }))
+}
+
+fn parse_text(input: Input<'_>) -> IResult<'_, RawLine<'_>> {
     map(str_until_newline, RawLine::Text)(input)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use test_case::test_case;
+
+    #[test_case(RawLine::Text(Cow::Borrowed("plain text")), "plain text" ; "text")]
+    #[test_case(RawLine::Text(Cow::Borrowed("# not a heading")), " # not a heading" ; "text like a heading")]
+    #[test_case(RawLine::Text(Cow::Borrowed("=>not a link")), " =>not a link" ; "text like a link")]
+    #[test_case(RawLine::Text(Cow::Borrowed("```")), " ```" ; "text like a toggle")]
+    #[test_case(RawLine::Text(Cow::Borrowed("*not an item")), "*not an item" ; "text with an asterisk")]
+    #[test_case(RawLine::Blockquote(Cow::Borrowed("quoted")), "> quoted" ; "blockquote")]
+    #[test_case(RawLine::ListItem(Cow::Borrowed("item")), "* item" ; "list item")]
+    #[test_case(
+        RawLine::Preformatted(Preformatted { alt_text: None, text: Cow::Borrowed("") }),
+        "```\n```" ;
+        "empty preformatted"
+    )]
+    #[test_case(
+        RawLine::Preformatted(Preformatted { alt_text: Some(Cow::Borrowed("alt")), text: Cow::Borrowed("a\n\nb") }),
+        "```alt\na\n\nb\n```" ;
+        "preformatted with alt text"
+    )]
+    fn test_display(line: RawLine<'static>, expected: &str) {
+        assert_eq!(line.to_string(), expected);
+    }
+
+    #[test]
+    fn test_serialize_terminates_every_line() {
+        let lines = [RawLine::Text(Cow::Borrowed("")), RawLine::Text(Cow::Borrowed("a"))];
+        assert_eq!(serialize_lines(&lines), "\na\n");
+
+        let mut written = Vec::new();
+        write_lines(&mut written, &lines).expect("writing to a Vec should never fail");
+        assert_eq!(written, b"\na\n");
+    }
+
+    fn preformatted(alt_text: Option<&'static str>, text: &'static str) -> RawLine<'static> {
+        RawLine::Preformatted(Preformatted { alt_text: alt_text.map(Cow::Borrowed), text: Cow::Borrowed(text) })
+    }
+
+    #[test_case("a\r", "a\r\r\n" ; "text ending with cr")]
+    #[test_case("a\r\r\nb", "a\r\r\nb\n" ; "crlf after cr")]
+    #[test_case("```\r\nx\r\r\n```", "```\nx\r\r\n```\n" ; "preformatted body ending with cr")]
+    #[test_case("```a\r\r\n```", "```a\r\r\n```\n" ; "alt text ending with cr")]
+    fn test_serialize_keeps_trailing_cr(input: &str, expected: &str) {
+        let lines = parse_lines(input);
+        let serialized = serialize_lines(&lines);
+        assert_eq!(serialized, expected);
+        assert_eq!(parse_lines(&serialized), lines);
+    }
+
+    #[test_case("```\n```\n", vec![preformatted(None, "")], vec![] ; "empty")]
+    #[test_case("```\n\n```", vec![preformatted(None, "")], vec![] ; "single empty line")]
+    #[test_case(
+        "```alt\r\n# code\r\n\r\n```\r\ntext",
+        vec![preformatted(Some("alt"), "# code\r\n"), RawLine::Text(Cow::Borrowed("text"))],
+        vec![] ;
+        "crlf line endings"
+    )]
+    #[test_case(
+        "````\ncode\n```",
+        vec![preformatted(Some("`"), "code")],
+        vec![] ;
+        "extra backtick is alt text"
+    )]
+    #[test_case(
+        "```\ncode\n``` trailing\ntext",
+        vec![preformatted(None, "code"), RawLine::Text(Cow::Borrowed("text"))],
+        vec![Warning { line: 3, kind: WarningKind::TextAfterClosingToggle(String::from(" trailing")) }] ;
+        "closing toggle with text"
+    )]
+    #[test_case(
+        "text\n```alt\ncode\n=> /link\n",
+        vec![RawLine::Text(Cow::Borrowed("text")), preformatted(Some("alt"), "code\n=> /link")],
+        vec![Warning { line: 2, kind: WarningKind::UnclosedPreformatted }] ;
+        "unclosed"
+    )]
+    #[test_case(
+        "```",
+        vec![preformatted(None, "")],
+        vec![Warning { line: 1, kind: WarningKind::UnclosedPreformatted }] ;
+        "lone toggle at eof"
+    )]
+    fn test_parse_preformatted(input: &str, expected: Vec<RawLine<'static>>, expected_warnings: Vec<Warning>) {
+        let (lines, warnings) = parse_lines_with_warnings(input);
+        assert_eq!(lines, expected);
+        assert_eq!(warnings, expected_warnings);
+    }
+
+    proptest! {
+        #[test]
+        fn test_parse_never_fails(input in "\\PC*(\n\\PC*){0,5}") {
+            parse_lines(&input);
+        }
+
+        #[test]
+        fn test_reparse_is_stable(input in "(?s:.){0,200}") {
+            let lines = parse_lines(&input);
+            let serialized = serialize_lines(&lines);
+            prop_assert_eq!(parse_lines(&serialized), lines);
+        }
+    }
+}
@@ -100,20 +100,6 @@ pub(crate) fn better_all_consuming<'s, O, F>(mut parser: F) -> impl FnMut(Input<
     }
 }
 
-pub(crate) fn repeated_all_consuming<'s, O, F>(parser: F) -> impl FnMut(Input<'s>) -> IResult<'s, Vec<O>>
-    where F: Parser<Input<'s>, O, Error<'s>>
-{
-    let mut parser = many_till(parser, eof);
-    move |input| {
-        let (leftover, (parsed, _eof)) = parser.parse(input)?;
-        if leftover.is_empty() {
-            Ok((leftover, parsed))
-        } else {
-            Err(nom::Err::Error(Error::new(leftover, ErrorKind::ExpectedEOF)))
-        }
-    }
-}
-
 pub(crate) fn better_tag(literal: &'static str) -> impl Fn(Input<'_>) -> IResult<'_, Input<'_>> {
     move |input| {
         tag(literal)(input).map_err(|err| {
@@ -124,14 +110,14 @@ pub(crate) fn better_tag(literal: &'static str) -> impl Fn(Input<'_>) -> IResult
     }
 }
 
-pub(crate) fn line_end(input: Input<'_>) -> IResult<Input<'_>> {
+pub(crate) fn line_end(input: Input<'_>) -> IResult<'_, Input<'_>> {
     context(
         "a line ending or EOF",
         alt((line_ending, eof))
     )(input)
 }
 
-pub(crate) fn str_until_newline(input: Input<'_>) -> IResult<Cow<'_, str>> {
+pub(crate) fn str_until_newline(input: Input<'_>) -> IResult<'_, Cow<'_, str>> {
     let str_parser = context(
         "a line of text",
         recognize(many_till(anychar, peek(line_end))),
@@ -148,11 +134,11 @@ pub(crate) fn str_until_newline(input: Input<'_>) -> IResult<Cow<'_, str>> {
     )(input)
 }
 
-pub(crate) fn optional_str_until_newline(input: Input<'_>) -> IResult<Option<Cow<'_, str>>> {
+pub(crate) fn optional_str_until_newline(input: Input<'_>) -> IResult<'_, Option<Cow<'_, str>>> {
     map(str_until_newline, |s| (!s.is_empty()).then_some(s))(input)
 }
 
-pub(crate) fn line_with_leader(lead: &'static str) -> impl Fn(Input<'_>) -> IResult<Cow<'_, str>> {
+pub(crate) fn line_with_leader(lead: &'static str) -> impl Fn(Input<'_>) -> IResult<'_, Cow<'_, str>> {
     move |input| {
         preceded(
             pair(
@@ -171,7 +157,7 @@ mod macros {
                 type Err = $crate::gemtext::Error<'static>;
                 fn from_str(input: &str) -> Result<Self, Self::Err> {
                     let input = $crate::gemtext::parser::Input::new(input);
-                    ::nom::Finish::finish($name::parse(input))
+                    ::nom::Finish::finish($crate::gemtext::parser::better_all_consuming($name::parse)(input))
                         .map_err($crate::gemtext::Error::into_static)
                         .map(|(_, value)| value)
                         .map($name::into_static)
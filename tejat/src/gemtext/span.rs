@@ -0,0 +1,92 @@
+use std::ops::Range;
+
+use super::parser::Input;
+
+/// The location of a parsed line in its source.
+///
+/// A span never includes the final line ending. Only [`RawLine::Preformatted`](super::RawLine::Preformatted)
+/// can span more than one line.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Span {
+    /// Byte offsets into the source.
+    pub bytes: Range<usize>,
+    /// The 1-based number of the first line.
+    pub line: u32,
+    /// The 1-based number of the last line.
+    pub end_line: u32,
+    /// 1-based columns, counted in characters like [`Error`](super::Error) does. The start
+    /// column is on `line` and the (exclusive) end column is on `end_line`.
+    pub columns: Range<usize>,
+}
+
+impl Span {
+    /// Returns the span of the text in `source` between `start` and `rest`.
+    pub(crate) fn between(source: &str, start: Input<'_>, rest: Input<'_>) -> Self {
+        let start_offset = start.location_offset();
+        let text = &source[start_offset..rest.location_offset()];
+        let text = text.strip_suffix('\n').map(|s| s.strip_suffix('\r').unwrap_or(s)).unwrap_or(text);
+        let last_line = text.rsplit('\n').next().unwrap_or(text);
+        let start_column = start.get_utf8_column();
+        let end_column = if last_line.len() == text.len() {
+            start_column + last_line.chars().count()
+        } else {
+            1 + last_line.chars().count()
+        };
+
+        Self {
+            bytes: start_offset..start_offset + text.len(),
+            line: start.location_line(),
+            end_line: start.location_line() + text.matches('\n').count() as u32,
+            columns: start_column..end_column,
+        }
+    }
+}
+
+/// A value along with where it was found in the source.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gemtext::{parse_lines, parse_lines_with_spans, Span};
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    fn span(bytes: std::ops::Range<usize>, line: u32, end_line: u32, columns: std::ops::Range<usize>) -> Span {
+        Span { bytes, line, end_line, columns }
+    }
+
+    #[test_case("", vec![] ; "empty")]
+    #[test_case("\n", vec![span(0..0, 1, 1, 1..1)] ; "empty line")]
+    #[test_case(
+        "# Heading\r\n=> /link\ntext",
+        vec![span(0..9, 1, 1, 1..10), span(11..19, 2, 2, 1..9), span(20..24, 3, 3, 1..5)] ;
+        "single lines"
+    )]
+    #[test_case(
+        "# Ünïcödé\n> é",
+        vec![span(0..13, 1, 1, 1..10), span(14..18, 2, 2, 1..4)] ;
+        "columns count characters"
+    )]
+    #[test_case(
+        "text\n```alt\ncode\nmore code\n```\n",
+        vec![span(0..4, 1, 1, 1..5), span(5..30, 2, 5, 1..4)] ;
+        "preformatted"
+    )]
+    #[test_case(
+        "```\ncode\n",
+        vec![span(0..8, 1, 2, 1..5)] ;
+        "unclosed preformatted"
+    )]
+    fn test_spans(input: &str, expected: Vec<Span>) {
+        let (lines, _) = parse_lines_with_spans(input);
+        let spans: Vec<_> = lines.iter().map(|line| line.span.clone()).collect();
+        assert_eq!(spans, expected);
+
+        let values: Vec<_> = lines.into_iter().map(|line| line.value).collect();
+        assert_eq!(values, parse_lines(input));
+    }
+}
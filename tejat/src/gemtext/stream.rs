@@ -0,0 +1,249 @@
+use std::{collections::VecDeque, io::{self, BufRead}};
+
+use super::{parse_single_line, Preformatted, RawLine, PREFORMATTED_TOGGLE};
+
+fn strip_line_ending(line: &str) -> &str {
+    line.strip_suffix("\r\n")
+        .or_else(|| line.strip_suffix('\n'))
+        .unwrap_or(line)
+}
+
+#[derive(Debug)]
+struct OpenPreformatted {
+    alt_text: Option<String>,
+    /// Raw body lines, including their line endings.
+    body: String,
+}
+
+impl OpenPreformatted {
+    fn close(self) -> RawLine<'static> {
+        RawLine::Preformatted(Preformatted {
+            alt_text: self.alt_text.map(Into::into),
+            text: strip_line_ending(&self.body).to_string().into(),
+        })
+    }
+}
+
+/// The I/O-independent part of streaming parsing: turns whole lines into [`RawLine`]s while
+/// remembering whether a preformatted block is open.
+#[derive(Debug, Default)]
+struct LineState {
+    parsed: VecDeque<RawLine<'static>>,
+    preformatted: Option<OpenPreformatted>,
+}
+
+impl LineState {
+    /// Accepts one line of input, including its line ending (if any).
+    fn push_line(&mut self, raw: &str) {
+        let line = strip_line_ending(raw);
+        match (&mut self.preformatted, line.strip_prefix(PREFORMATTED_TOGGLE)) {
+            (Some(_), Some(_)) => {
+                let open = self.preformatted.take().expect("block is open");
+                self.parsed.push_back(open.close());
+            }
+            (Some(open), None) => open.body.push_str(raw),
+            (None, Some(alt_text)) => {
+                self.preformatted = Some(OpenPreformatted {
+                    alt_text: (!alt_text.is_empty()).then(|| alt_text.to_string()),
+                    body: String::new(),
+                });
+            }
+            (None, None) => self.parsed.push_back(parse_single_line(line).into_static()),
+        }
+    }
+
+    /// Signals the end of input, which closes any open preformatted block.
+    fn finish(&mut self) {
+        if let Some(open) = self.preformatted.take() {
+            self.parsed.push_back(open.close());
+        }
+    }
+}
+
+/// Parses gemtext from a [`BufRead`] one line at a time, yielding each [`RawLine`] as soon as
+/// it is complete.
+///
+/// Produces the same lines as [`parse_lines`](super::parse_lines) would for the whole input,
+/// regardless of how the reader splits the data into chunks. Preformatted blocks are yielded
+/// once their closing toggle line, or the end of input, has been read.
+#[derive(Debug)]
+pub struct StreamParser<R> {
+    reader: R,
+    buffer: String,
+    state: LineState,
+    done: bool,
+}
+
+impl<R: BufRead> StreamParser<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+            state: LineState::default(),
+            done: false,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> Iterator for StreamParser<R> {
+    type Item = io::Result<RawLine<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.state.parsed.pop_front() {
+                return Some(Ok(line));
+            } else if self.done {
+                return None;
+            }
+
+            self.buffer.clear();
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => {
+                    self.state.finish();
+                    self.done = true;
+                }
+                Ok(_) => self.state.push_line(&self.buffer),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// The asynchronous equivalent of [`StreamParser`], reading from a
+/// [`tokio::io::AsyncBufRead`].
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct AsyncStreamParser<R> {
+    reader: R,
+    buffer: String,
+    state: LineState,
+    done: bool,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncBufRead + Unpin> AsyncStreamParser<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+            state: LineState::default(),
+            done: false,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Returns the next parsed line, or `None` once the reader is exhausted.
+    pub async fn next_line(&mut self) -> io::Result<Option<RawLine<'static>>> {
+        use tokio::io::AsyncBufReadExt;
+
+        loop {
+            if let Some(line) = self.state.parsed.pop_front() {
+                return Ok(Some(line));
+            } else if self.done {
+                return Ok(None);
+            }
+
+            self.buffer.clear();
+            if self.reader.read_line(&mut self.buffer).await? == 0 {
+                self.state.finish();
+                self.done = true;
+            } else {
+                self.state.push_line(&self.buffer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemtext::{parse_lines, Heading};
+    use pretty_assertions::assert_eq;
+    use proptest::prelude::*;
+    use std::io::BufReader;
+    use test_case::test_case;
+
+    fn parse_streaming(input: &str, capacity: usize) -> Vec<RawLine<'static>> {
+        StreamParser::new(BufReader::with_capacity(capacity, input.as_bytes()))
+            .collect::<io::Result<_>>()
+            .expect("reading from a slice should never fail")
+    }
+
+    fn parse_whole(input: &str) -> Vec<RawLine<'static>> {
+        parse_lines(input)
+            .into_iter()
+            .map(RawLine::into_static)
+            .collect()
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("\n" ; "single empty line")]
+    #[test_case("# Heading\n=> /link Link\n* item\n> quote\ntext" ; "no trailing newline")]
+    #[test_case("```alt\nline 1\r\n\nline 3\n```\ntext\n" ; "preformatted")]
+    #[test_case("```\n```" ; "empty preformatted at eof")]
+    #[test_case("```alt\n# not a heading\n```ignored\ntext\n" ; "closing toggle with text")]
+    #[test_case("text\n```alt\n# not a heading\n\n" ; "unclosed preformatted")]
+    fn test_matches_parse_lines(input: &str) {
+        let expected = parse_whole(input);
+        for capacity in [1, 2, 3, 7, 8192] {
+            assert_eq!(parse_streaming(input, capacity), expected, "buffer capacity {}", capacity);
+        }
+    }
+
+    struct BrokenPipe;
+
+    impl io::Read for BrokenPipe {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn test_yields_lines_before_input_is_complete() {
+        let reader = BufReader::new(io::Read::chain("# Title\n".as_bytes(), BrokenPipe));
+        let mut parser = StreamParser::new(reader);
+        assert_eq!(parser.next().unwrap().unwrap(), RawLine::Heading(Heading::H1("Title".into())));
+        assert_eq!(parser.next().unwrap().unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_an_error() {
+        let mut parser = StreamParser::new(&b"\xff\xfe\n"[..]);
+        let err = parser.next().expect("should yield an item").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    proptest! {
+        #[test]
+        fn test_matches_parse_lines_for_any_chunking(
+            lines in prop::collection::vec(
+                prop::sample::select(vec!["```", "```alt", "# heading", "=> /link text", "* item", "> quote", "text", ""]),
+                0..20,
+            ),
+            crlf in any::<bool>(),
+            capacity in 1..16usize,
+        ) {
+            let input = lines.join(if crlf { "\r\n" } else { "\n" });
+            prop_assert_eq!(parse_streaming(&input, capacity), parse_whole(&input));
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_matches_parse_lines() {
+        let input = "# Heading\n```alt\ncode\n```\n=> gemini://example.com Example\n```\nunclosed";
+        let mut parser = AsyncStreamParser::new(tokio::io::BufReader::with_capacity(3, input.as_bytes()));
+        let mut parsed = Vec::new();
+        while let Some(line) = parser.next_line().await.expect("reading from a slice should never fail") {
+            parsed.push(line);
+        }
+        assert_eq!(parsed, parse_whole(input));
+    }
+}
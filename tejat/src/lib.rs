@@ -1,5 +1,14 @@
+#[cfg(feature = "tokio")]
+pub mod codec;
+pub mod gemini_mime;
 pub mod gemtext;
+pub mod lint;
 pub use mime;
+pub mod proto;
+pub mod render;
+pub mod request;
+pub mod response;
+pub mod sanitize;
 pub mod status;
 pub mod uri;
 
@@ -7,7 +16,7 @@ pub fn mime_is_gemtext(m: &mime::Mime) -> bool {
     m.essence_str().eq_ignore_ascii_case("text/gemini")
 }
 
-pub fn mime_charset(m: &mime::Mime) -> mime::Name {
+pub fn mime_charset(m: &mime::Mime) -> mime::Name<'_> {
     m.get_param(mime::CHARSET).unwrap_or(mime::UTF_8)
 }
 
@@ -0,0 +1,427 @@
+//! Checks gemtext documents for common mistakes.
+
+use std::{fmt, ops::Range};
+
+use url::Url;
+
+use crate::gemtext::{parse_lines_with_spans, LinkTarget, RawLine, Span, WarningKind};
+
+/// Prefixes that look like a link line, but are not one.
+const LINK_LIKE_PREFIXES: [&str; 2] = ["=>", "= >"];
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Info => write!(f, "info"),
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Identifies which check produced a [`Diagnostic`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Code {
+    /// A heading with more than three `#`, which is parsed as a level 3 heading.
+    HeadingTooDeep,
+    /// A link line whose target is not a valid URL or relative reference.
+    InvalidLinkTarget,
+    /// A link line without a target.
+    MissingLinkTarget,
+    /// A preformatted block that is never closed.
+    UnclosedPreformatted,
+    /// Text after a closing preformatted toggle, which is ignored.
+    ToggleTrailingText,
+    /// A text line that looks like a list item using `-`, or `*` without a space.
+    ListMarker,
+    /// Whitespace at the end of a line outside of preformatted blocks.
+    TrailingWhitespace,
+    /// A text line that looks like a link line, but is not one.
+    LinkLikeText,
+    /// A line longer than the configured maximum.
+    LineTooLong,
+}
+
+impl Code {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::HeadingTooDeep => "heading-too-deep",
+            Self::InvalidLinkTarget => "invalid-link-target",
+            Self::MissingLinkTarget => "missing-link-target",
+            Self::UnclosedPreformatted => "unclosed-preformatted",
+            Self::ToggleTrailingText => "toggle-trailing-text",
+            Self::ListMarker => "list-marker",
+            Self::TrailingWhitespace => "trailing-whitespace",
+            Self::LinkLikeText => "link-like-text",
+            Self::LineTooLong => "line-too-long",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::InvalidLinkTarget | Self::MissingLinkTarget => Severity::Error,
+            Self::HeadingTooDeep
+            | Self::UnclosedPreformatted
+            | Self::ToggleTrailingText
+            | Self::ListMarker
+            | Self::LinkLikeText => Severity::Warning,
+            Self::TrailingWhitespace | Self::LineTooLong => Severity::Info,
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A suggested edit: replace the text at `span` with `replacement`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Fix {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Code,
+    pub message: String,
+    pub span: Span,
+    pub fix: Option<Fix>,
+}
+
+impl Diagnostic {
+    fn new(code: Code, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            severity: code.severity(),
+            code,
+            message: message.into(),
+            span,
+            fix: None,
+        }
+    }
+
+    fn with_fix(self, message: impl Into<String>, span: Span, replacement: impl Into<String>) -> Self {
+        Self {
+            fix: Some(Fix {
+                message: message.into(),
+                span,
+                replacement: replacement.into(),
+            }),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}[{}]: {}",
+            self.span.line, self.span.columns.start, self.severity, self.code, self.message
+        )
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Options {
+    /// The maximum number of characters in any line. Text is wrapped by clients, so this is
+    /// disabled by default.
+    pub max_line_length: Option<usize>,
+    /// The maximum number of characters in a line of a preformatted block, which clients
+    /// display without wrapping.
+    pub max_preformatted_width: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_line_length: None,
+            max_preformatted_width: Some(80),
+        }
+    }
+}
+
+/// Finds the byte offsets of each line in the source.
+struct SourceLines<'s> {
+    source: &'s str,
+    starts: Vec<usize>,
+}
+
+impl<'s> SourceLines<'s> {
+    fn new(source: &'s str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { source, starts }
+    }
+
+    /// Returns the 1-based line `number`, without its line ending.
+    fn line(&self, number: u32) -> &'s str {
+        let index = number as usize - 1;
+        let end = self.starts.get(index + 1).map_or(self.source.len(), |next| next - 1);
+        let line = &self.source[self.starts[index]..end];
+        line.strip_suffix('\r').unwrap_or(line)
+    }
+
+    /// Returns the span of the bytes in `range` within line `number`.
+    fn span(&self, number: u32, range: Range<usize>) -> Span {
+        let line = self.line(number);
+        let start = self.starts[number as usize - 1];
+        Span {
+            bytes: start + range.start..start + range.end,
+            line: number,
+            end_line: number,
+            columns: line[..range.start].chars().count() + 1..line[..range.end].chars().count() + 1,
+        }
+    }
+
+    /// Returns an empty span at the very end of the source.
+    fn end(&self) -> Span {
+        let number = self.starts.len() as u32;
+        let len = self.line(number).len();
+        self.span(number, len..len)
+    }
+}
+
+/// Checks `source` for problems, returning diagnostics ordered by their position.
+pub fn lint(source: &str, options: &Options) -> Vec<Diagnostic> {
+    let lines = SourceLines::new(source);
+    let (parsed, warnings) = parse_lines_with_spans(source);
+    let mut diagnostics = Vec::new();
+    let max_preformatted_width = match (options.max_line_length, options.max_preformatted_width) {
+        (Some(line), Some(preformatted)) => Some(line.min(preformatted)),
+        (line, preformatted) => line.or(preformatted),
+    };
+
+    for line in &parsed {
+        let span = &line.span;
+        if let RawLine::Preformatted(_) = &line.value {
+            for number in span.line + 1..=span.end_line {
+                let text = lines.line(number);
+                // The closing toggle line is the only line that can start with the toggle.
+                if !text.starts_with("```") {
+                    check_length(&lines, number, max_preformatted_width, &mut diagnostics);
+                }
+            }
+            continue;
+        }
+
+        let number = span.line;
+        let text = lines.line(number);
+        match &line.value {
+            RawLine::Heading(_) => check_heading(&lines, number, text, &mut diagnostics),
+            RawLine::Link(link) => check_link_target(&lines, number, link.target(), &mut diagnostics),
+            RawLine::Text(_) => check_text(&lines, number, text, &mut diagnostics),
+            _ => {}
+        }
+        check_trailing_whitespace(&lines, number, text, &mut diagnostics);
+        check_length(&lines, number, options.max_line_length, &mut diagnostics);
+    }
+
+    for warning in warnings {
+        let text = lines.line(warning.line);
+        match warning.kind {
+            WarningKind::UnclosedPreformatted => {
+                let replacement = if source.ends_with('\n') { "```\n" } else { "\n```\n" };
+                diagnostics.push(
+                    Diagnostic::new(
+                        Code::UnclosedPreformatted,
+                        "preformatted block is never closed",
+                        lines.span(warning.line, 0..text.len()),
+                    )
+                    .with_fix("close the block at the end of the document", lines.end(), replacement),
+                );
+            }
+            WarningKind::TextAfterClosingToggle(_) => {
+                let span = lines.span(warning.line, 3..text.len());
+                diagnostics.push(
+                    Diagnostic::new(Code::ToggleTrailingText, "text after a closing toggle is ignored", span.clone())
+                        .with_fix("remove the text", span, ""),
+                );
+            }
+        }
+    }
+
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.span.bytes.start, diagnostic.code));
+    diagnostics
+}
+
+fn check_heading(lines: &SourceLines<'_>, number: u32, text: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let depth = text.len() - text.trim_start_matches('#').len();
+    if depth > 3 {
+        let span = lines.span(number, 0..depth);
+        diagnostics.push(
+            Diagnostic::new(
+                Code::HeadingTooDeep,
+                format!("gemtext headings have at most 3 levels, this is parsed as a level 3 heading starting with {:?}", &text[3..depth]),
+                span.clone(),
+            )
+            .with_fix("use a level 3 heading", span, "###"),
+        );
+    }
+}
+
+fn check_link_target(lines: &SourceLines<'_>, number: u32, target: &LinkTarget<'_>, diagnostics: &mut Vec<Diagnostic>) {
+    let LinkTarget::Relative(target) = target else {
+        return;
+    };
+
+    let text = lines.line(number);
+    let span = lines.span(number, 0..text.len());
+    if target.is_empty() {
+        diagnostics.push(Diagnostic::new(Code::MissingLinkTarget, "link line has no URL", span));
+        return;
+    }
+
+    let base = Url::parse("gemini://example.invalid/").expect("base URL should be valid");
+    if let Err(err) = base.join(target) {
+        let start = text.find(target.as_ref()).unwrap_or(0);
+        diagnostics.push(Diagnostic::new(
+            Code::InvalidLinkTarget,
+            format!("invalid link target {:?}: {}", target, err),
+            lines.span(number, start..start + target.len()),
+        ));
+    }
+}
+
+fn check_text(lines: &SourceLines<'_>, number: u32, text: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let marker_len = if text.starts_with("- ") {
+        Some(2)
+    } else if text.starts_with('*') && text[1..].starts_with(|c: char| !c.is_whitespace() && c != '*') {
+        Some(1)
+    } else {
+        None
+    };
+    if let Some(marker_len) = marker_len {
+        let span = lines.span(number, 0..marker_len);
+        diagnostics.push(
+            Diagnostic::new(Code::ListMarker, "list items must start with \"* \"", span.clone())
+                .with_fix("use a gemtext list item", span, "* "),
+        );
+    }
+
+    let trimmed = text.trim_start();
+    if let Some(prefix) = LINK_LIKE_PREFIXES.iter().find(|prefix| trimmed.starts_with(*prefix)) {
+        let span = lines.span(number, 0..text.len() - trimmed.len() + prefix.len());
+        diagnostics.push(
+            Diagnostic::new(Code::LinkLikeText, "text line looks like a link line, but is not one", span.clone())
+                .with_fix("make it a link line", span, "=>"),
+        );
+    }
+}
+
+fn check_trailing_whitespace(lines: &SourceLines<'_>, number: u32, text: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let trimmed_len = text.trim_end().len();
+    if trimmed_len < text.len() {
+        let span = lines.span(number, trimmed_len..text.len());
+        diagnostics.push(
+            Diagnostic::new(Code::TrailingWhitespace, "trailing whitespace", span.clone())
+                .with_fix("remove the trailing whitespace", span, ""),
+        );
+    }
+}
+
+fn check_length(lines: &SourceLines<'_>, number: u32, max: Option<usize>, diagnostics: &mut Vec<Diagnostic>) {
+    let Some(max) = max else {
+        return;
+    };
+
+    let text = lines.line(number);
+    let len = text.chars().count();
+    if len > max {
+        diagnostics.push(Diagnostic::new(
+            Code::LineTooLong,
+            format!("line is {} characters long, more than the maximum of {}", len, max),
+            lines.span(number, 0..text.len()),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use proptest::prelude::*;
+    use test_case::test_case;
+
+    fn codes(source: &str) -> Vec<(u32, Code)> {
+        lint(source, &Options::default())
+            .into_iter()
+            .map(|diagnostic| (diagnostic.span.line, diagnostic.code))
+            .collect()
+    }
+
+    /// Applies every suggested fix, assuming none of them overlap.
+    fn apply_fixes(source: &str) -> String {
+        let mut fixed = source.to_string();
+        let mut fixes: Vec<_> = lint(source, &Options::default()).into_iter().filter_map(|d| d.fix).collect();
+        fixes.sort_by_key(|fix| std::cmp::Reverse(fix.span.bytes.start));
+        for fix in fixes {
+            fixed.replace_range(fix.span.bytes, &fix.replacement);
+        }
+        fixed
+    }
+
+    #[test]
+    fn test_clean_document() {
+        let source = "# Title\n\nSome text.\n=> gemini://example.com Example\n* item\n> quote\n```\ncode  \n```\n";
+        assert_eq!(codes(source), vec![]);
+    }
+
+    #[test_case("#### Deep\n", vec![(1, Code::HeadingTooDeep)], "### Deep\n" ; "heading too deep")]
+    #[test_case("=>\n", vec![(1, Code::MissingLinkTarget)], "=>\n" ; "missing link target")]
+    #[test_case("=> http://[::1 text\n", vec![(1, Code::InvalidLinkTarget)], "=> http://[::1 text\n" ; "invalid link target")]
+    #[test_case("```\ncode\n", vec![(1, Code::UnclosedPreformatted)], "```\ncode\n```\n" ; "unclosed preformatted")]
+    #[test_case("```\ncode", vec![(1, Code::UnclosedPreformatted)], "```\ncode\n```\n" ; "unclosed preformatted without newline")]
+    #[test_case("```\ncode\n``` end\n", vec![(3, Code::ToggleTrailingText)], "```\ncode\n```\n" ; "toggle trailing text")]
+    #[test_case("- item\n*item\n", vec![(1, Code::ListMarker), (2, Code::ListMarker)], "* item\n* item\n" ; "list markers")]
+    #[test_case("**bold** text\n* item\n", vec![], "**bold** text\n* item\n" ; "emphasis is not a list marker")]
+    #[test_case("text  \n# Heading\t\r\n", vec![(1, Code::TrailingWhitespace), (2, Code::TrailingWhitespace)], "text\n# Heading\r\n" ; "trailing whitespace")]
+    #[test_case("  => /link\n= > /other\n", vec![(1, Code::LinkLikeText), (2, Code::LinkLikeText)], "=> /link\n=> /other\n" ; "link like text")]
+    fn test_lint(source: &str, expected: Vec<(u32, Code)>, fixed: &str) {
+        assert_eq!(codes(source), expected);
+        assert_eq!(apply_fixes(source), fixed);
+    }
+
+    #[test]
+    fn test_line_length() {
+        let long = "x".repeat(81);
+        let source = format!("{long}\n```\n{long}\n```\n");
+        assert_eq!(codes(&source), vec![(3, Code::LineTooLong)]);
+
+        let options = Options { max_line_length: Some(10), max_preformatted_width: None };
+        let found: Vec<_> = lint(&source, &options).into_iter().map(|d| (d.span.line, d.code)).collect();
+        assert_eq!(found, vec![(1, Code::LineTooLong), (3, Code::LineTooLong)]);
+    }
+
+    #[test]
+    fn test_spans_point_at_problem() {
+        let diagnostics = lint("é text \n", &Options::default());
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.severity, Severity::Info);
+        assert_eq!(diagnostic.span, Span { bytes: 7..8, line: 1, end_line: 1, columns: 7..8 });
+        assert_eq!(diagnostic.to_string(), "1:7: info[trailing-whitespace]: trailing whitespace");
+    }
+
+    proptest! {
+        #[test]
+        fn test_lint_never_panics(source in "(\\PC|\r|\n|```|#|\\*|=>|-)*") {
+            let options = Options { max_line_length: Some(5), ..Options::default() };
+            for diagnostic in lint(&source, &options) {
+                prop_assert!(source.get(diagnostic.span.bytes.clone()).is_some());
+            }
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! The Gemini protocol as state machines that do no I/O themselves.
+//!
+//! [`Client`] and [`Server`] are fed the bytes received from the other side (with `receive`
+//! and `receive_eof`) and produce the bytes to send (with the `send_*` methods). What happened
+//! on the connection is reported as [`Event`]s, taken with `poll_event`. This lets blocking,
+//! async and in-memory transports share one implementation of the protocol.
+//!
+//! A connection carries a single request, followed by a response header and, for successful
+//! responses, a body that ends when the server closes the connection.
+
+use std::collections::VecDeque;
+
+use crate::request::{self, Request};
+use crate::status::{self, LenientStatus, Status};
+
+#[derive(Debug, Eq, PartialEq, thiserror::Error)]
+pub enum Error {
+    #[error("invalid request: {0}")]
+    InvalidRequest(request::Error),
+    #[error("invalid response header: {0}")]
+    InvalidHeader(status::Error),
+    /// The other side sent data where the protocol allows none, e.g. after a request.
+    #[error("received unexpected data")]
+    UnexpectedData,
+    /// The connection was closed before a complete request or header was received.
+    #[error("connection closed early")]
+    ClosedEarly,
+    /// A `send_*` method was called when the protocol does not allow it, e.g. sending a
+    /// second response header.
+    #[error("not allowed in the current connection state")]
+    InvalidState,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Event {
+    /// The server received a complete, valid request.
+    RequestReceived(Request),
+    /// The client received a complete, valid response header.
+    HeaderReceived(Status),
+    /// The client received part of the response body.
+    BodyChunk(Vec<u8>),
+    /// The other side closed the connection.
+    Closed,
+    /// The other side broke the protocol. A client should close the connection; a server
+    /// can still answer invalid requests, usually with a `59` status.
+    ProtocolError(Error),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ClientState {
+    Idle,
+    AwaitingHeader,
+    ReceivingBody,
+    /// The header of a response without a body was received.
+    Done,
+    Closed,
+}
+
+/// The client side of a connection.
+#[derive(Debug)]
+pub struct Client {
+    state: ClientState,
+    buffer: Vec<u8>,
+    events: VecDeque<Event>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self {
+            state: ClientState::Idle,
+            buffer: Vec::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Returns the bytes to send for `request`. Only one request can be sent per connection.
+    pub fn send_request(&mut self, request: &Request) -> Result<Vec<u8>, Error> {
+        if self.state != ClientState::Idle {
+            return Err(Error::InvalidState);
+        }
+        self.state = ClientState::AwaitingHeader;
+        Ok(request.to_bytes())
+    }
+
+    /// Handles bytes received from the server.
+    pub fn receive(&mut self, data: &[u8]) {
+        match self.state {
+            ClientState::AwaitingHeader => {
+                self.buffer.extend_from_slice(data);
+                self.parse_header();
+            }
+            ClientState::ReceivingBody if !data.is_empty() => self.events.push_back(Event::BodyChunk(data.to_vec())),
+            ClientState::Idle | ClientState::Done if !data.is_empty() => self.fail(Error::UnexpectedData),
+            _ => {}
+        }
+    }
+
+    /// Parses the header once it is complete. Unknown codes are treated as the base code of their
+    /// category, see [`Status::parse_lenient`].
+    fn parse_header(&mut self) {
+        match Status::parse_header_lenient(&self.buffer) {
+            Ok((LenientStatus { status, .. }, len)) => {
+                let body = self.buffer.split_off(len);
+                self.buffer = Vec::new();
+                self.state = if status.is_success() { ClientState::ReceivingBody } else { ClientState::Done };
+                self.events.push_back(Event::HeaderReceived(status));
+                if !body.is_empty() {
+                    self.receive(&body);
+                }
+            }
+            Err(status::Error::Incomplete) => {}
+            Err(error) => self.fail(Error::InvalidHeader(error)),
+        }
+    }
+
+    fn fail(&mut self, error: Error) {
+        self.state = ClientState::Closed;
+        self.events.push_back(Event::ProtocolError(error));
+    }
+
+    /// Handles the server closing the connection, which ends the response body.
+    pub fn receive_eof(&mut self) {
+        match self.state {
+            ClientState::AwaitingHeader => self.fail(Error::ClosedEarly),
+            ClientState::Closed => return,
+            _ => {}
+        }
+        self.state = ClientState::Closed;
+        self.events.push_back(Event::Closed);
+    }
+
+    pub fn poll_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state == ClientState::Closed
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ServerState {
+    AwaitingRequest,
+    AwaitingResponse,
+    SendingBody,
+    Closed,
+}
+
+/// The server side of a connection.
+#[derive(Debug)]
+pub struct Server {
+    state: ServerState,
+    buffer: Vec<u8>,
+    events: VecDeque<Event>,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self {
+            state: ServerState::AwaitingRequest,
+            buffer: Vec::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Handles bytes received from the client.
+    pub fn receive(&mut self, data: &[u8]) {
+        match self.state {
+            ServerState::AwaitingRequest => {
+                self.buffer.extend_from_slice(data);
+                match Request::parse(&self.buffer) {
+                    Ok((request, len)) => {
+                        let rest = self.buffer.len() - len;
+                        self.buffer = Vec::new();
+                        self.state = ServerState::AwaitingResponse;
+                        self.events.push_back(Event::RequestReceived(request));
+                        if rest > 0 {
+                            self.events.push_back(Event::ProtocolError(Error::UnexpectedData));
+                        }
+                    }
+                    Err(request::Error::Incomplete) => {}
+                    Err(error) => {
+                        self.buffer = Vec::new();
+                        self.state = ServerState::AwaitingResponse;
+                        self.events.push_back(Event::ProtocolError(Error::InvalidRequest(error)));
+                    }
+                }
+            }
+            ServerState::AwaitingResponse | ServerState::SendingBody if !data.is_empty() => {
+                self.events.push_back(Event::ProtocolError(Error::UnexpectedData));
+            }
+            _ => {}
+        }
+    }
+
+    /// Handles the client closing the connection.
+    pub fn receive_eof(&mut self) {
+        match self.state {
+            ServerState::AwaitingRequest => self.events.push_back(Event::ProtocolError(Error::ClosedEarly)),
+            ServerState::Closed => return,
+            _ => {}
+        }
+        self.state = ServerState::Closed;
+        self.events.push_back(Event::Closed);
+    }
+
+    /// Returns the bytes to send for the response header. After a success header, the body
+    /// can be sent with [`Server::send_body`]. Otherwise, the connection should be closed.
+    ///
+    /// The header can be sent before a complete request was received, e.g. to reject a request
+    /// that is too slow to arrive. Statuses whose meta is too long or contains a CR or LF are
+    /// rejected, as they would not be read back as the same header.
+    pub fn send_header(&mut self, status: &Status) -> Result<Vec<u8>, Error> {
+        if !matches!(self.state, ServerState::AwaitingRequest | ServerState::AwaitingResponse) {
+            return Err(Error::InvalidState);
+        }
+        status.validate().map_err(Error::InvalidHeader)?;
+        let header = status.to_string();
+        self.state = if status.is_success() { ServerState::SendingBody } else { ServerState::Closed };
+        Ok(header.into_bytes())
+    }
+
+    /// Returns the bytes to send for a part of the response body.
+    pub fn send_body(&mut self, chunk: &[u8]) -> Result<Vec<u8>, Error> {
+        if self.state != ServerState::SendingBody {
+            return Err(Error::InvalidState);
+        }
+        Ok(chunk.to_vec())
+    }
+
+    /// Marks the response as complete. The connection should be closed afterwards.
+    pub fn finish(&mut self) {
+        self.state = ServerState::Closed;
+    }
+
+    pub fn poll_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state == ServerState::Closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::{PermanentFailure, MAX_HEADER_LENGTH, MAX_META_LENGTH};
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    fn events(poll: impl FnMut() -> Option<Event>) -> Vec<Event> {
+        std::iter::from_fn(poll).collect()
+    }
+
+    fn request() -> Request {
+        Request::from("gemini://example.com/".parse::<crate::uri::GeminiUrl>().unwrap())
+    }
+
+    /// Runs a whole exchange, delivering the data `chunk_size` bytes at a time.
+    #[test_case(1 ; "byte by byte")]
+    #[test_case(7 ; "small chunks")]
+    #[test_case(4096 ; "all at once")]
+    fn test_exchange(chunk_size: usize) {
+        let mut client = Client::new();
+        let mut server = Server::new();
+
+        let request_bytes = client.send_request(&request()).unwrap();
+        for chunk in request_bytes.chunks(chunk_size) {
+            server.receive(chunk);
+        }
+        assert_eq!(events(|| server.poll_event()), [Event::RequestReceived(request())]);
+
+        let status: Status = "20 text/gemini".parse().unwrap();
+        let mut response = server.send_header(&status).unwrap();
+        response.extend(server.send_body(b"# Hello\n").unwrap());
+        response.extend(server.send_body(b"world\n").unwrap());
+        server.finish();
+        assert!(server.is_closed());
+
+        for chunk in response.chunks(chunk_size) {
+            client.receive(chunk);
+        }
+        client.receive_eof();
+        assert!(client.is_closed());
+
+        let events = events(|| client.poll_event());
+        assert_eq!(events.first(), Some(&Event::HeaderReceived(status)));
+        assert_eq!(events.last(), Some(&Event::Closed));
+        let body: Vec<u8> = events.into_iter()
+            .filter_map(|event| match event {
+                Event::BodyChunk(chunk) => Some(chunk),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        assert_eq!(body, b"# Hello\nworld\n");
+    }
+
+    #[test]
+    fn test_client_response_without_body() {
+        let mut client = Client::new();
+        client.send_request(&request()).unwrap();
+        client.receive(b"51 Not found\r\n");
+        client.receive_eof();
+        assert_eq!(events(|| client.poll_event()), [
+            Event::HeaderReceived("51 Not found".parse().unwrap()),
+            Event::Closed,
+        ]);
+    }
+
+    #[test]
+    fn test_client_unknown_code() {
+        let mut client = Client::new();
+        client.send_request(&request()).unwrap();
+        client.receive(b"45 Busy\r\n");
+        client.receive_eof();
+        assert_eq!(events(|| client.poll_event()), [
+            Event::HeaderReceived("40 Busy".parse().unwrap()),
+            Event::Closed,
+        ]);
+    }
+
+    #[test_case(b"20 text/gemini", vec![Event::ProtocolError(Error::ClosedEarly), Event::Closed] ; "closed during header")]
+    #[test_case(b"99 Nope\r\n", vec![Event::ProtocolError(Error::InvalidHeader(status::Error::UnknownCode(99)))] ; "invalid header")]
+    #[test_case(b"20 text/gemini\nbody", vec![Event::ProtocolError(Error::InvalidHeader(status::Error::BareLf))] ; "bare lf")]
+    #[test_case(b"51 Not found\r\nbody", vec![
+        Event::HeaderReceived("51 Not found".parse().unwrap()),
+        Event::ProtocolError(Error::UnexpectedData),
+    ] ; "body after failure")]
+    fn test_client_errors(data: &[u8], expected: Vec<Event>) {
+        let mut client = Client::new();
+        client.send_request(&request()).unwrap();
+        client.receive(data);
+        client.receive_eof();
+        assert_eq!(events(|| client.poll_event()), expected);
+        assert!(client.is_closed());
+    }
+
+    #[test]
+    fn test_client_single_request() {
+        let mut client = Client::new();
+        client.receive(b"20\r\n");
+        assert_eq!(client.poll_event(), Some(Event::ProtocolError(Error::UnexpectedData)));
+
+        let mut client = Client::new();
+        client.send_request(&request()).unwrap();
+        assert_eq!(client.send_request(&request()), Err(Error::InvalidState));
+    }
+
+    #[test_case(b"/relative\r\n", vec![
+        Event::ProtocolError(Error::InvalidRequest(request::Error::NotAbsolute)),
+        Event::Closed,
+    ] ; "invalid request")]
+    #[test_case(b"gemini://example.com/\r\nmore", vec![
+        Event::RequestReceived(request()),
+        Event::ProtocolError(Error::UnexpectedData),
+        Event::Closed,
+    ] ; "data after request")]
+    #[test_case(b"gemini://example.com/", vec![Event::ProtocolError(Error::ClosedEarly), Event::Closed] ; "closed during request")]
+    #[test_case(b"gemini://example.com/\n", vec![
+        Event::ProtocolError(Error::InvalidRequest(request::Error::BareLf)),
+        Event::Closed,
+    ] ; "bare lf")]
+    fn test_server_errors(data: &[u8], expected: Vec<Event>) {
+        let mut server = Server::new();
+        server.receive(data);
+        server.receive_eof();
+        assert_eq!(events(|| server.poll_event()), expected);
+        assert!(server.is_closed());
+    }
+
+    #[test]
+    fn test_server_can_reject_invalid_requests() {
+        let mut server = Server::new();
+        server.receive(&[b'a'; 2000]);
+        assert_eq!(server.poll_event(), Some(Event::ProtocolError(Error::InvalidRequest(request::Error::TooLong))));
+
+        let header = server.send_header(&"59 Bad request".parse().unwrap()).unwrap();
+        assert_eq!(header, b"59 Bad request\r\n");
+        assert_eq!(server.send_body(b"body"), Err(Error::InvalidState));
+        assert_eq!(server.send_header(&"59 Again".parse().unwrap()), Err(Error::InvalidState));
+    }
+
+    #[test]
+    fn test_server_header_too_long() {
+        let mut server = Server::new();
+        server.receive(&request().to_bytes());
+        let status = not_found("a".repeat(MAX_META_LENGTH + 1));
+        assert_eq!(server.send_header(&status), Err(Error::InvalidHeader(status::Error::MetaTooLong)));
+
+        let status = not_found("a".repeat(MAX_META_LENGTH));
+        assert_eq!(server.send_header(&status).unwrap().len(), MAX_HEADER_LENGTH);
+    }
+
+    /// A status built by hand, skipping the checks of [`Status::not_found`].
+    fn not_found(message: String) -> Status {
+        Status::PermanentFailure(PermanentFailure::NotFound { message })
+    }
+
+    #[test]
+    fn test_server_header_injection() {
+        let mut server = Server::new();
+        server.receive(&request().to_bytes());
+        let status = not_found("x\r\n20 text/html".to_string());
+        assert_eq!(
+            server.send_header(&status),
+            Err(Error::InvalidHeader(status::Error::InvalidMeta("x\r\n20 text/html".to_string())))
+        );
+        assert_eq!(server.send_header(&"51 Not found".parse().unwrap()).unwrap(), b"51 Not found\r\n");
+    }
+}
@@ -0,0 +1,290 @@
+//! Renders gemtext as HTML5.
+//!
+//! All text from the document is escaped, and links using schemes that can run code in a
+//! browser (`javascript:` and friends) are rendered without an `href`.
+
+use std::fmt::{self, Write};
+
+use url::Url;
+
+use crate::gemtext::{Block, Document, Heading, Link, LinkTarget, RawLine};
+
+const UNSAFE_SCHEMES: [&str; 3] = ["javascript", "vbscript", "data"];
+
+/// CSS class names to add to each kind of element. `None` adds no `class` attribute.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Classes {
+    pub h1: Option<String>,
+    pub h2: Option<String>,
+    pub h3: Option<String>,
+    pub paragraph: Option<String>,
+    pub link: Option<String>,
+    pub list: Option<String>,
+    pub quote: Option<String>,
+    pub preformatted: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Mode {
+    /// Only the elements for the document's content, to embed in another page.
+    #[default]
+    Fragment,
+    /// A complete HTML document.
+    Page {
+        /// The page title. Defaults to the text of the first heading.
+        title: Option<String>,
+        /// The URL of a stylesheet to link to.
+        stylesheet: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Options {
+    /// The URL the document was fetched from, used to resolve relative links.
+    pub base: Option<Url>,
+    pub classes: Classes,
+    pub mode: Mode,
+}
+
+/// Writes `text` with the characters that are special in HTML text and attributes escaped.
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = self.0;
+        while let Some(index) = rest.find(['&', '<', '>', '"', '\'']) {
+            f.write_str(&rest[..index])?;
+            f.write_str(match rest.as_bytes()[index] {
+                b'&' => "&amp;",
+                b'<' => "&lt;",
+                b'>' => "&gt;",
+                b'"' => "&quot;",
+                _ => "&#39;",
+            })?;
+            rest = &rest[index + 1..];
+        }
+        f.write_str(rest)
+    }
+}
+
+/// Writes the opening tag for `element`, with a class attribute if configured.
+struct Open<'a>(&'a str, &'a Option<String>);
+
+impl fmt::Display for Open<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.1 {
+            Some(class) => write!(f, "<{} class=\"{}\">", self.0, Escaped(class)),
+            None => write!(f, "<{}>", self.0),
+        }
+    }
+}
+
+/// Renders `lines` as HTML.
+pub fn render(lines: &[RawLine<'_>], options: &Options) -> String {
+    render_document(&Document::from(lines.to_vec()), options)
+}
+
+/// Renders an already grouped [`Document`] as HTML.
+pub fn render_document(document: &Document<'_>, options: &Options) -> String {
+    let mut output = String::new();
+    write_document(&mut output, document, options).expect("writing to a String should never fail");
+    output
+}
+
+fn write_document<W: Write>(out: &mut W, document: &Document<'_>, options: &Options) -> fmt::Result {
+    if let Mode::Page { title, stylesheet } = &options.mode {
+        let title = title.as_deref().or_else(|| {
+            document.blocks().iter().find_map(|block| match block {
+                Block::Heading(heading) => Some(heading.text()),
+                _ => None,
+            })
+        });
+
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, "<html>")?;
+        writeln!(out, "<head>")?;
+        writeln!(out, "<meta charset=\"utf-8\">")?;
+        writeln!(out, "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">")?;
+        if let Some(title) = title {
+            writeln!(out, "<title>{}</title>", Escaped(title))?;
+        }
+        if let Some(stylesheet) = stylesheet {
+            writeln!(out, "<link rel=\"stylesheet\" href=\"{}\">", Escaped(stylesheet))?;
+        }
+        writeln!(out, "</head>")?;
+        writeln!(out, "<body>")?;
+    }
+
+    let classes = &options.classes;
+    for block in document.blocks() {
+        match block {
+            Block::Paragraph(lines) => {
+                for line in lines {
+                    writeln!(out, "{}{}</p>", Open("p", &classes.paragraph), Escaped(line))?;
+                }
+            }
+            Block::Blank(_) => {}
+            Block::Heading(heading) => {
+                let (tag, class) = match heading {
+                    Heading::H1(_) => ("h1", &classes.h1),
+                    Heading::H2(_) => ("h2", &classes.h2),
+                    Heading::H3(_) => ("h3", &classes.h3),
+                };
+                writeln!(out, "{}{}</{}>", Open(tag, class), Escaped(heading.text()), tag)?;
+            }
+            Block::LinkGroup(links) => {
+                for link in links {
+                    write_link(out, link, options)?;
+                }
+            }
+            Block::List(items) => {
+                writeln!(out, "{}", Open("ul", &classes.list))?;
+                for item in items {
+                    writeln!(out, "<li>{}</li>", Escaped(item))?;
+                }
+                writeln!(out, "</ul>")?;
+            }
+            Block::Quote(lines) => {
+                writeln!(out, "{}", Open("blockquote", &classes.quote))?;
+                for line in lines {
+                    writeln!(out, "<p>{}</p>", Escaped(line))?;
+                }
+                writeln!(out, "</blockquote>")?;
+            }
+            Block::Preformatted(pre) => {
+                write!(out, "<pre")?;
+                if let Some(class) = &classes.preformatted {
+                    write!(out, " class=\"{}\"", Escaped(class))?;
+                }
+                if let Some(alt_text) = &pre.alt_text {
+                    write!(out, " aria-label=\"{}\"", Escaped(alt_text))?;
+                }
+                // A newline directly after <pre> is dropped by HTML parsers, so an extra one
+                // keeps a leading empty line in the text.
+                writeln!(out, ">\n{}</pre>", Escaped(&pre.text))?;
+            }
+        }
+    }
+
+    if let Mode::Page { .. } = options.mode {
+        writeln!(out, "</body>")?;
+        writeln!(out, "</html>")?;
+    }
+
+    Ok(())
+}
+
+fn write_link<W: Write>(out: &mut W, link: &Link<'_>, options: &Options) -> fmt::Result {
+    let resolved;
+    let (href, is_safe) = match link.target() {
+        LinkTarget::Absolute(url) => (url.as_str(), !UNSAFE_SCHEMES.contains(&url.scheme())),
+        LinkTarget::Relative(target) => match options.base.as_ref().map(|base| base.join(target)) {
+            Some(Ok(url)) => {
+                resolved = url;
+                (resolved.as_str(), !UNSAFE_SCHEMES.contains(&resolved.scheme()))
+            }
+            _ => (target.as_ref(), true),
+        },
+    };
+    let text = link.text().unwrap_or(href);
+
+    write!(out, "{}", Open("p", &options.classes.paragraph))?;
+    if is_safe {
+        write!(out, "<a href=\"{}\"", Escaped(href))?;
+    } else {
+        write!(out, "<a")?;
+    }
+    if let Some(class) = &options.classes.link {
+        write!(out, " class=\"{}\"", Escaped(class))?;
+    }
+    writeln!(out, ">{}</a></p>", Escaped(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemtext::parse_lines;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    fn fragment(source: &str) -> String {
+        render(&parse_lines(source), &Options::default())
+    }
+
+    #[test_case("# One\n## Two\n### Three\n", "<h1>One</h1>\n<h2>Two</h2>\n<h3>Three</h3>\n" ; "headings")]
+    #[test_case("text\n\nmore\n", "<p>text</p>\n<p>more</p>\n" ; "blank lines are skipped")]
+    #[test_case("* a\n* b\n\n* c\n", "<ul>\n<li>a</li>\n<li>b</li>\n</ul>\n<ul>\n<li>c</li>\n</ul>\n" ; "lists")]
+    #[test_case("> a\n> b\n", "<blockquote>\n<p>a</p>\n<p>b</p>\n</blockquote>\n" ; "quotes")]
+    #[test_case("```alt \"text\"\n\ncode\n```\n", "<pre aria-label=\"alt &quot;text&quot;\">\n\ncode</pre>\n" ; "preformatted")]
+    #[test_case("<script>&'\"\n", "<p>&lt;script&gt;&amp;&#39;&quot;</p>\n" ; "escaping")]
+    #[test_case(
+        "=> gemini://example.com Example\n=> /relative\n",
+        "<p><a href=\"gemini://example.com\">Example</a></p>\n<p><a href=\"/relative\">/relative</a></p>\n" ;
+        "links without base"
+    )]
+    #[test_case(
+        "=> javascript:alert(1) <b>click</b>\n",
+        "<p><a>&lt;b&gt;click&lt;/b&gt;</a></p>\n" ;
+        "unsafe links have no href"
+    )]
+    fn test_render_fragment(source: &str, expected: &str) {
+        assert_eq!(fragment(source), expected);
+    }
+
+    #[test]
+    fn test_resolves_relative_links() {
+        let options = Options {
+            base: Some(Url::parse("gemini://example.com/dir/page.gmi").unwrap()),
+            ..Options::default()
+        };
+        let html = render(&parse_lines("=> other.gmi Other\n=> /root\n"), &options);
+        assert_eq!(
+            html,
+            "<p><a href=\"gemini://example.com/dir/other.gmi\">Other</a></p>\n\
+             <p><a href=\"gemini://example.com/root\">gemini://example.com/root</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_classes() {
+        let options = Options {
+            classes: Classes {
+                h1: Some(String::from("title")),
+                paragraph: Some(String::from("para")),
+                link: Some(String::from("link")),
+                list: Some(String::from("list")),
+                quote: Some(String::from("quote")),
+                preformatted: Some(String::from("pre")),
+                ..Classes::default()
+            },
+            ..Options::default()
+        };
+        let html = render(&parse_lines("# T\n## U\ntext\n=> /a A\n* i\n> q\n```\nc\n```\n"), &options);
+        assert_eq!(
+            html,
+            "<h1 class=\"title\">T</h1>\n\
+             <h2>U</h2>\n\
+             <p class=\"para\">text</p>\n\
+             <p class=\"para\"><a href=\"/a\" class=\"link\">A</a></p>\n\
+             <ul class=\"list\">\n<li>i</li>\n</ul>\n\
+             <blockquote class=\"quote\">\n<p>q</p>\n</blockquote>\n\
+             <pre class=\"pre\">\nc</pre>\n"
+        );
+    }
+
+    #[test]
+    fn test_full_page() {
+        let options = Options {
+            mode: Mode::Page { title: None, stylesheet: Some(String::from("/style.css")) },
+            ..Options::default()
+        };
+        let html = render(&parse_lines("text\n# A & B\n"), &options);
+        assert_eq!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+             <title>A &amp; B</title>\n<link rel=\"stylesheet\" href=\"/style.css\">\n\
+             </head>\n<body>\n<p>text</p>\n<h1>A &amp; B</h1>\n</body>\n</html>\n"
+        );
+    }
+}
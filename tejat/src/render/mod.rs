@@ -0,0 +1,4 @@
+//! Converts gemtext into other presentation formats.
+
+pub mod html;
+pub mod terminal;
@@ -0,0 +1,281 @@
+//! Renders gemtext as lines of text for a terminal.
+//!
+//! Text is word-wrapped to a fixed number of columns, measured with the display width of each
+//! character so that wide (e.g. CJK) characters take up two columns. Preformatted text is never
+//! wrapped. Links are numbered in order, and [`Rendered::links`] maps the numbers back to the
+//! link targets.
+//!
+//! The renderer does not filter the document's text. Pass documents from untrusted sources
+//! through [`sanitize`](crate::sanitize) first.
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::gemtext::{Heading, RawLine};
+
+/// SGR parameters (the part between `ESC [` and `m`, e.g. `1;35`) to style each line type with.
+/// `None` leaves that line type unstyled.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Theme {
+    pub h1: Option<String>,
+    pub h2: Option<String>,
+    pub h3: Option<String>,
+    pub link: Option<String>,
+    pub link_number: Option<String>,
+    pub list: Option<String>,
+    pub quote: Option<String>,
+    pub preformatted: Option<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            h1: Some(String::from("1;35")),
+            h2: Some(String::from("1;36")),
+            h3: Some(String::from("1;34")),
+            link: Some(String::from("4;34")),
+            link_number: Some(String::from("2")),
+            list: None,
+            quote: Some(String::from("2;3")),
+            preformatted: Some(String::from("33")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Mode {
+    /// Plain text without escape sequences.
+    #[default]
+    Plain,
+    /// Text styled with ANSI escape sequences.
+    Ansi(Theme),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Options {
+    /// The number of columns to wrap text at.
+    pub width: usize,
+    pub mode: Mode,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            mode: Mode::default(),
+        }
+    }
+}
+
+/// The output of [`render`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Rendered {
+    /// The lines to display, without line endings.
+    pub lines: Vec<String>,
+    /// The targets of the document's links. Link `[n]` is at index `n - 1`.
+    pub links: Vec<String>,
+}
+
+/// Splits `text` into lines of at most `width` columns, breaking at whitespace where possible.
+///
+/// Runs of whitespace are collapsed. Words wider than `width` are broken between characters.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+
+    for word in text.split_whitespace() {
+        let word_width = word.width();
+        if line_width > 0 && line_width + 1 + word_width <= width {
+            line.push(' ');
+            line.push_str(word);
+            line_width += 1 + word_width;
+            continue;
+        }
+
+        if line_width > 0 {
+            lines.push(std::mem::take(&mut line));
+            line_width = 0;
+        }
+        if word_width <= width {
+            line.push_str(word);
+            line_width = word_width;
+            continue;
+        }
+
+        for c in word.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if line_width > 0 && line_width + char_width > width {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            line.push(c);
+            line_width += char_width;
+        }
+    }
+
+    if line_width > 0 || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+struct Renderer<'o> {
+    options: &'o Options,
+    rendered: Rendered,
+}
+
+impl Renderer<'_> {
+    fn theme(&self) -> Option<&Theme> {
+        match &self.options.mode {
+            Mode::Plain => None,
+            Mode::Ansi(theme) => Some(theme),
+        }
+    }
+
+    fn styled(&self, text: &str, style: impl Fn(&Theme) -> &Option<String>) -> String {
+        match self.theme().and_then(|theme| style(theme).as_ref()) {
+            Some(sgr) if !text.is_empty() => format!("\x1b[{}m{}\x1b[0m", sgr, text),
+            _ => text.to_string(),
+        }
+    }
+
+    /// Wraps `text` after `prefix`, indenting continuation lines by `prefix_width` columns so
+    /// they line up with the first one.
+    fn push_wrapped(
+        &mut self,
+        prefix: &str,
+        prefix_width: usize,
+        text: &str,
+        style: impl Fn(&Theme) -> &Option<String>,
+    ) {
+        let indent = " ".repeat(prefix_width);
+        for (index, line) in wrap(text, self.options.width.saturating_sub(prefix_width)).into_iter().enumerate() {
+            let line_prefix = if index == 0 { prefix } else { &indent };
+            let line = if line.is_empty() {
+                line_prefix.trim_end().to_string()
+            } else {
+                format!("{}{}", line_prefix, self.styled(&line, &style))
+            };
+            self.rendered.lines.push(line);
+        }
+    }
+
+    fn push_line(&mut self, line: &RawLine<'_>) {
+        match line {
+            RawLine::Text(text) => self.push_wrapped("", 0, text, |_| &None),
+            RawLine::Heading(heading) => {
+                let text = format!("{} {}", "#".repeat(heading.level() as usize), heading.text());
+                self.push_wrapped("", 0, &text, |theme| match heading {
+                    Heading::H1(_) => &theme.h1,
+                    Heading::H2(_) => &theme.h2,
+                    Heading::H3(_) => &theme.h3,
+                });
+            }
+            RawLine::Link(link) => {
+                self.rendered.links.push(link.target().as_str().to_string());
+                let number = format!("[{}]", self.rendered.links.len());
+                let prefix = format!("{} ", self.styled(&number, |theme| &theme.link_number));
+                let text = link.text().unwrap_or_else(|| link.target().as_str());
+                self.push_wrapped(&prefix, number.len() + 1, text, |theme| &theme.link);
+            }
+            RawLine::ListItem(item) => {
+                let prefix = format!("{} ", self.styled("•", |theme| &theme.list));
+                self.push_wrapped(&prefix, 2, item, |_| &None);
+            }
+            RawLine::Blockquote(text) => {
+                // Every line of a quote is marked, and the style covers the marker too.
+                for line in wrap(text, self.options.width.saturating_sub(2)) {
+                    let line = format!("> {}", line);
+                    let line = self.styled(line.trim_end(), |theme| &theme.quote);
+                    self.rendered.lines.push(line);
+                }
+            }
+            RawLine::Preformatted(pre) => {
+                let lines = Some(pre.text.as_ref()).filter(|text| !text.is_empty());
+                for line in lines.into_iter().flat_map(|text| text.split('\n')) {
+                    let line = line.strip_suffix('\r').unwrap_or(line);
+                    let line = self.styled(line, |theme| &theme.preformatted);
+                    self.rendered.lines.push(line);
+                }
+            }
+        }
+    }
+}
+
+/// Renders `lines` for a terminal.
+pub fn render(lines: &[RawLine<'_>], options: &Options) -> Rendered {
+    let mut renderer = Renderer {
+        options,
+        rendered: Rendered::default(),
+    };
+    for line in lines {
+        renderer.push_line(line);
+    }
+    renderer.rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemtext::parse_lines;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    fn plain(source: &str, width: usize) -> Vec<String> {
+        render(&parse_lines(source), &Options { width, mode: Mode::Plain }).lines
+    }
+
+    #[test_case("", 10, vec![""] ; "empty text")]
+    #[test_case("one two three", 9, vec!["one two", "three"] ; "wraps at whitespace")]
+    #[test_case("  spaced   out  ", 20, vec!["spaced out"] ; "collapses whitespace")]
+    #[test_case("abcdefghij", 4, vec!["abcd", "efgh", "ij"] ; "breaks long words")]
+    #[test_case("日本語の文章", 5, vec!["日本", "語の", "文章"] ; "wide characters")]
+    #[test_case("a b", 0, vec!["a", "b"] ; "zero width")]
+    fn test_wrap(text: &str, width: usize, expected: Vec<&str>) {
+        assert_eq!(wrap(text, width), expected);
+    }
+
+    #[test_case("# Heading text\n", 10, vec!["# Heading", "text"] ; "headings")]
+    #[test_case("* list item text\n* \n", 10, vec!["• list", "  item", "  text", "•"] ; "list items")]
+    #[test_case("> quoted text here\n", 10, vec!["> quoted", "> text", "> here"] ; "quotes")]
+    #[test_case(
+        "=> /a Link text here\n=> gemini://example.com\n",
+        12,
+        vec!["[1] Link", "    text", "    here", "[2] gemini:/", "    /example", "    .com"] ;
+        "links"
+    )]
+    #[test_case(
+        "```\na long preformatted line\n\n```\n",
+        5,
+        vec!["a long preformatted line", ""] ;
+        "preformatted text is not wrapped"
+    )]
+    #[test_case("```\r\ncode\r\nmore\r\n```\r\n", 10, vec!["code", "more"] ; "preformatted crlf line endings")]
+    fn test_render_plain(source: &str, width: usize, expected: Vec<&str>) {
+        assert_eq!(plain(source, width), expected);
+    }
+
+    #[test]
+    fn test_link_targets() {
+        let rendered = render(&parse_lines("=> /a A\ntext\n=> gemini://example.com/b\n"), &Options::default());
+        assert_eq!(rendered.links, ["/a", "gemini://example.com/b"]);
+    }
+
+    #[test]
+    fn test_render_ansi() {
+        let options = Options {
+            width: 20,
+            mode: Mode::Ansi(Theme::default()),
+        };
+        let rendered = render(&parse_lines("# Title\ntext\n=> /a A link\n* item\n> quote\n```\ncode\n```\n"), &options);
+        assert_eq!(rendered.lines, [
+            "\x1b[1;35m# Title\x1b[0m",
+            "text",
+            "\x1b[2m[1]\x1b[0m \x1b[4;34mA link\x1b[0m",
+            "• item",
+            "\x1b[2;3m> quote\x1b[0m",
+            "\x1b[33mcode\x1b[0m",
+        ]);
+    }
+}
@@ -0,0 +1,174 @@
+//! Gemini requests, which consist of a single absolute URL followed by CRLF.
+
+use std::fmt;
+
+use url::Url;
+
+use crate::uri::{GeminiUrl, MAX_URL_LENGTH};
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+const CRLF: &[u8] = b"\r\n";
+
+#[derive(Debug, Eq, PartialEq, thiserror::Error)]
+pub enum Error {
+    #[error("request starts with a byte order mark")]
+    ByteOrderMark,
+    /// The URL is longer than [`MAX_URL_LENGTH`] bytes.
+    #[error("request URL is longer than {MAX_URL_LENGTH} bytes")]
+    TooLong,
+    /// The input ends before the CRLF, so more of it has to be read.
+    #[error("request is incomplete")]
+    Incomplete,
+    /// The line ends with a LF that is not preceded by a CR.
+    #[error("request ends with LF instead of CRLF")]
+    BareLf,
+    #[error("request is not valid UTF-8")]
+    InvalidUtf8,
+    /// The URL contains a control character, like a tab or CR, which URL parsing would drop.
+    #[error("request URL contains a control character")]
+    ControlCharacter,
+    /// The URL is relative or empty.
+    #[error("request URL is not absolute")]
+    NotAbsolute,
+    #[error("invalid request URL: {0}")]
+    InvalidUrl(url::ParseError),
+}
+
+/// A request for the resource at an absolute URL.
+///
+/// The URL does not have to be a `gemini://` URL, as servers may act as proxies for other
+/// schemes.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Request {
+    url: Url,
+}
+
+impl Request {
+    /// Creates a request for `url`, removing its fragment, which is never sent.
+    pub fn new(mut url: Url) -> Result<Self, Error> {
+        url.set_fragment(None);
+        if url.as_str().len() > MAX_URL_LENGTH {
+            return Err(Error::TooLong);
+        }
+        Ok(Self { url })
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn into_url(self) -> Url {
+        self.url
+    }
+
+    /// Parses the request line at the start of `bytes`.
+    ///
+    /// Returns the request and the number of bytes of the line, including the CRLF. Anything
+    /// after the line is left alone. Like [`Request::new`], the URL's fragment is removed.
+    pub fn parse(bytes: &[u8]) -> Result<(Self, usize), Error> {
+        if bytes.starts_with(BOM) {
+            return Err(Error::ByteOrderMark);
+        }
+
+        const MAX_LINE_LENGTH: usize = MAX_URL_LENGTH + CRLF.len();
+        let limit = bytes.len().min(MAX_LINE_LENGTH);
+        let Some(lf) = bytes[..limit].iter().position(|b| *b == b'\n') else {
+            return Err(if limit == MAX_LINE_LENGTH { Error::TooLong } else { Error::Incomplete });
+        };
+        let Some(end) = lf.checked_sub(1).filter(|cr| bytes[*cr] == b'\r') else {
+            return Err(Error::BareLf);
+        };
+
+        let line = std::str::from_utf8(&bytes[..end]).map_err(|_| Error::InvalidUtf8)?;
+        if line.bytes().any(|b| b.is_ascii_control()) {
+            return Err(Error::ControlCharacter);
+        }
+        let url = Url::parse(line).map_err(|error| match error {
+            url::ParseError::RelativeUrlWithoutBase => Error::NotAbsolute,
+            error => Error::InvalidUrl(error),
+        })?;
+        // Parsing can make the URL longer, e.g. by percent-encoding spaces.
+        Self::new(url).map(|request| (request, end + CRLF.len()))
+    }
+
+    /// Returns the request as it is sent over the network.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+}
+
+impl From<GeminiUrl> for Request {
+    fn from(url: GeminiUrl) -> Self {
+        // Gemini URLs are already limited in length, not counting the fragment.
+        Self { url: url.without_fragment().as_url().clone() }
+    }
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\r\n", self.url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    #[test_case(b"gemini://example.com/\r\n", "gemini://example.com/", 23 ; "gemini url")]
+    #[test_case(b"https://example.com/path?q\r\nbody", "https://example.com/path?q", 28 ; "proxy request with trailing data")]
+    #[test_case(b"gemini://example.com/page#part\r\n", "gemini://example.com/page", 32 ; "fragment is removed")]
+    fn test_parse_valid(bytes: &[u8], url: &str, consumed: usize) {
+        let (request, len) = Request::parse(bytes).unwrap();
+        assert_eq!(request.url().as_str(), url);
+        assert_eq!(len, consumed);
+    }
+
+    #[test_case(b"\xef\xbb\xbfgemini://example.com/\r\n", Error::ByteOrderMark ; "byte order mark")]
+    #[test_case(b"gemini://example.com/", Error::Incomplete ; "no line ending")]
+    #[test_case(b"gemini://example.com/\r", Error::Incomplete ; "cr without lf")]
+    #[test_case(b"gemini://example.com/\n", Error::BareLf ; "bare lf")]
+    #[test_case(b"gemini://example.com/\nmore\r\n", Error::BareLf ; "bare lf before crlf")]
+    #[test_case(b"gemini://example.com/\ta\r\n", Error::ControlCharacter ; "tab")]
+    #[test_case(b"gemini://example.com/\r\r\n", Error::ControlCharacter ; "cr")]
+    #[test_case(b"\x01gemini://example.com/\r\n", Error::ControlCharacter ; "leading control character")]
+    #[test_case(b"gemini://example.com/\xff\r\n", Error::InvalidUtf8 ; "invalid utf8")]
+    #[test_case(b"/path\r\n", Error::NotAbsolute ; "relative url")]
+    #[test_case(b"\r\n", Error::NotAbsolute ; "empty url")]
+    #[test_case(b"gemini://[::1\r\n", Error::InvalidUrl(url::ParseError::InvalidIpv6Address) ; "invalid url")]
+    fn test_parse_invalid(bytes: &[u8], expected: Error) {
+        assert_eq!(Request::parse(bytes), Err(expected));
+    }
+
+    #[test]
+    fn test_length_limit() {
+        let prefix = "gemini://example.com/";
+        let longest = format!("{}{}", prefix, "a".repeat(MAX_URL_LENGTH - prefix.len()));
+        let (request, len) = Request::parse(format!("{}\r\n", longest).as_bytes()).unwrap();
+        assert_eq!(request.url().as_str(), longest);
+        assert_eq!(len, MAX_URL_LENGTH + 2);
+
+        assert_eq!(Request::parse(format!("{}a\r\n", longest).as_bytes()), Err(Error::TooLong));
+        assert_eq!(Request::parse(format!("{}a", longest).as_bytes()), Err(Error::Incomplete));
+        assert_eq!(Request::parse(format!("{}aa", longest).as_bytes()), Err(Error::TooLong));
+        assert_eq!(Request::new(Url::parse(&format!("{}a", longest)).unwrap()), Err(Error::TooLong));
+
+        // The line fits, but the space becomes `%20`.
+        let spaced = format!("{} {}\r\n", prefix, &longest[prefix.len() + 1..]);
+        assert_eq!(Request::parse(spaced.as_bytes()), Err(Error::TooLong));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let request = Request::new(Url::parse("gemini://example.com/page#fragment").unwrap()).unwrap();
+        assert_eq!(request.to_bytes(), b"gemini://example.com/page\r\n");
+        assert_eq!(Request::parse(&request.to_bytes()), Ok((request, 27)));
+    }
+
+    #[test]
+    fn test_from_gemini_url() {
+        let url: GeminiUrl = "gemini://example.com/#fragment".parse().unwrap();
+        assert_eq!(Request::from(url).to_string(), "gemini://example.com/\r\n");
+    }
+}
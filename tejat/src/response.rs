@@ -0,0 +1,180 @@
+//! Gemini responses: a status header, followed by a body for successful responses.
+
+use std::io::{self, Read};
+
+use encoding_rs::Encoding;
+
+use crate::gemtext::{parse_lines, RawLine};
+use crate::gemini_mime::GeminiMime;
+use crate::status::{self, Status, Success, MAX_HEADER_LENGTH};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("invalid response header: {0}")]
+    InvalidHeader(status::Error),
+    /// The body is larger than the limit passed to the reading method.
+    #[error("response body is larger than {limit} bytes")]
+    BodyTooLarge { limit: usize },
+    #[error("unknown charset {0:?}")]
+    UnknownCharset(String),
+    /// Only successful responses have a body.
+    #[error("response has status {}, not success", .0.code())]
+    NotSuccess(Status),
+    #[error("response is not gemtext, but {0}")]
+    NotGemtext(GeminiMime),
+}
+
+/// A response with its header already parsed and the body still to be read from `R`.
+#[derive(Debug)]
+pub struct Response<R> {
+    status: Status,
+    body: R,
+}
+
+impl<R: Read> Response<R> {
+    pub fn new(status: Status, body: R) -> Self {
+        Self { status, body }
+    }
+
+    /// Reads the response header from `reader`, leaving the body to be read.
+    ///
+    /// The header is read one byte at a time so that nothing after it is consumed. Wrap
+    /// unbuffered readers, like sockets, in a [`BufReader`](std::io::BufReader).
+    pub fn read(mut reader: R) -> Result<Self, Error> {
+        let mut header = Vec::with_capacity(64);
+        let mut byte = [0];
+        while header.last() != Some(&b'\n') && header.len() < MAX_HEADER_LENGTH {
+            match reader.read(&mut byte) {
+                Ok(0) => break,
+                Ok(_) => header.push(byte[0]),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        let (status, _) = Status::parse_header(&header).map_err(Error::InvalidHeader)?;
+        Ok(Self::new(status, reader))
+    }
+
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    /// The MIME type of the body, if this is a successful response.
+    pub fn mime(&self) -> Option<&GeminiMime> {
+        match &self.status {
+            Status::Success(success) => Some(success.mime()),
+            _ => None,
+        }
+    }
+
+    pub fn body_mut(&mut self) -> &mut R {
+        &mut self.body
+    }
+
+    pub fn into_parts(self) -> (Status, R) {
+        (self.status, self.body)
+    }
+
+    /// Reads the whole body, failing if it is larger than `limit` bytes.
+    pub fn read_bytes(&mut self, limit: usize) -> Result<Vec<u8>, Error> {
+        if !self.status.is_success() {
+            return Err(Error::NotSuccess(self.status.clone()));
+        }
+
+        let mut bytes = Vec::new();
+        (&mut self.body).take(limit as u64 + 1).read_to_end(&mut bytes)?;
+        if bytes.len() > limit {
+            return Err(Error::BodyTooLarge { limit });
+        }
+        Ok(bytes)
+    }
+
+    /// Reads the whole body as text, decoded from the charset given in the MIME type, or UTF-8
+    /// if there is none. Malformed sequences are replaced with `U+FFFD`.
+    ///
+    /// A byte order mark is removed if it matches the charset, but never overrides it.
+    pub fn read_text(&mut self, limit: usize) -> Result<String, Error> {
+        let charset = match &self.status {
+            Status::Success(Success::Normal { mime }) => mime.charset().as_str().to_string(),
+            status => return Err(Error::NotSuccess(status.clone())),
+        };
+        let encoding = Encoding::for_label(charset.as_bytes()).ok_or(Error::UnknownCharset(charset))?;
+
+        let bytes = self.read_bytes(limit)?;
+        let (text, _) = encoding.decode_with_bom_removal(&bytes);
+        Ok(text.into_owned())
+    }
+
+    /// Reads and parses the whole body, failing if it is not gemtext.
+    pub fn read_gemtext(&mut self, limit: usize) -> Result<Vec<RawLine<'static>>, Error> {
+        match self.mime() {
+            Some(mime) if !mime.is_gemtext() => return Err(Error::NotGemtext(mime.clone())),
+            _ => {}
+        }
+
+        let text = self.read_text(limit)?;
+        Ok(parse_lines(&text).into_iter().map(RawLine::into_static).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemtext::Heading;
+    use pretty_assertions::assert_eq;
+    use std::{borrow::Cow, io::Cursor};
+    use test_case::test_case;
+
+    fn response(bytes: &[u8]) -> Response<Cursor<&[u8]>> {
+        Response::read(Cursor::new(bytes)).expect("response should be valid")
+    }
+
+    #[test]
+    fn test_read_leaves_body() {
+        let mut response = response(b"20 text/plain\r\nbody");
+        assert_eq!(response.mime().map(GeminiMime::as_mime), Some(&mime::TEXT_PLAIN));
+        assert_eq!(response.read_bytes(100).unwrap(), b"body");
+    }
+
+    #[test_case(b"20 text/plain\r\n\xe6\x97\xa5\xe6\x9c\xac", "日本" ; "utf8 by default")]
+    #[test_case(b"20 text/plain; charset=UTF-8\r\n\xef\xbf\xbd\xff", "\u{fffd}\u{fffd}" ; "malformed utf8")]
+    #[test_case(b"20 text/plain; charset=iso-8859-1\r\n\xe9t\xe9", "été" ; "latin1")]
+    #[test_case(b"20 text/plain; charset=Shift_JIS\r\n\x93\xfa\x96\x7b", "日本" ; "shift jis")]
+    #[test_case(b"20 text/plain\r\n\xef\xbb\xbfabc", "abc" ; "utf8 bom is removed")]
+    #[test_case(b"20 text/plain; charset=iso-8859-1\r\n\xef\xbb\xbf\xe9t\xe9", "\u{ef}\u{bb}\u{bf}été" ; "bom does not override charset")]
+    fn test_read_text(bytes: &[u8], expected: &str) {
+        assert_eq!(response(bytes).read_text(100).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_read_gemtext() {
+        let mut response = response(b"20\r\n# Title\r\ntext\n");
+        assert_eq!(response.read_gemtext(100).unwrap(), [
+            RawLine::Heading(Heading::H1(Cow::Borrowed("Title"))),
+            RawLine::Text(Cow::Borrowed("text")),
+        ]);
+    }
+
+    #[test]
+    fn test_body_limit() {
+        assert_eq!(response(b"20\r\n12345").read_bytes(5).unwrap(), b"12345");
+        assert!(matches!(response(b"20\r\n123456").read_bytes(5), Err(Error::BodyTooLarge { limit: 5 })));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(response(b"51 Not found\r\n").read_text(100), Err(Error::NotSuccess(_))));
+        assert!(matches!(response(b"20 text/plain\r\n").read_gemtext(100), Err(Error::NotGemtext(_))));
+        assert!(matches!(
+            response(b"20 text/plain; charset=made-up\r\n").read_text(100),
+            Err(Error::UnknownCharset(charset)) if charset == "made-up"
+        ));
+        assert!(matches!(
+            Response::read(Cursor::new(b"20 text/gemini")),
+            Err(Error::InvalidHeader(status::Error::Incomplete))
+        ));
+    }
+}
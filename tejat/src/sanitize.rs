@@ -0,0 +1,236 @@
+//! Removes characters that could manipulate a terminal from untrusted gemtext.
+//!
+//! Documents fetched from arbitrary capsules can contain escape sequences that move the cursor,
+//! rewrite the window title or worse, as well as bidirectional overrides that make text display
+//! differently from what it contains. This module strips or escapes:
+//!
+//! - C0 control characters other than tab (and newlines between preformatted lines),
+//! - `DEL` and C1 control characters, and
+//! - bidirectional embeddings, overrides and isolates (`U+202A..=U+202E`, `U+2066..=U+2069`).
+//!
+//! An `ESC` starts an escape sequence, which is handled as a whole. Absolute link targets are
+//! left alone, as URL parsing already percent-encodes control characters.
+
+use std::borrow::Cow;
+
+use crate::gemtext::{Heading, Preformatted, RawLine};
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+
+/// What to do with unsafe characters.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Action {
+    /// Remove them, along with the rest of any escape sequence.
+    #[default]
+    Strip,
+    /// Replace them with a visible representation: caret notation (`^[`) for C0 control
+    /// characters and `DEL`, and `<U+XXXX>` for everything else.
+    Escape,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Policy {
+    pub action: Action,
+    /// Keep SGR sequences (colours and text attributes, like `ESC [ 1 ; 31 m`) in preformatted
+    /// text. Some capsules use them for ANSI art.
+    pub preformatted_sgr: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            action: Action::default(),
+            preformatted_sgr: true,
+        }
+    }
+}
+
+fn is_unsafe(c: char) -> bool {
+    (c.is_control() && c != '\t')
+        || ('\u{202a}'..='\u{202e}').contains(&c)
+        || ('\u{2066}'..='\u{2069}').contains(&c)
+}
+
+/// Returns the length in bytes of the escape sequence at the start of `s`, which starts with `ESC`.
+fn escape_sequence_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let len_while = |start: usize, range: std::ops::RangeInclusive<u8>| {
+        start + bytes[start..].iter().take_while(|b| range.contains(b)).count()
+    };
+
+    match bytes.get(1) {
+        // CSI: parameter bytes, intermediate bytes, then a final byte.
+        Some(b'[') => {
+            let end = len_while(len_while(2, 0x30..=0x3f), 0x20..=0x2f);
+            match bytes.get(end) {
+                Some(0x40..=0x7e) => end + 1,
+                _ => end,
+            }
+        }
+        // Control strings (OSC, DCS, SOS, PM, APC), terminated by BEL or ST (`ESC \`).
+        Some(b']' | b'P' | b'X' | b'^' | b'_') => {
+            let body = &s[2..];
+            match body.find([BEL, ESC]) {
+                Some(index) if body[index..].starts_with(BEL) => 2 + index + 1,
+                Some(index) if body[index..].starts_with("\x1b\\") => 2 + index + 2,
+                Some(index) => 2 + index,
+                None => s.len(),
+            }
+        }
+        Some(0x20..=0x7e) => {
+            let end = len_while(1, 0x20..=0x2f);
+            match bytes.get(end) {
+                Some(0x30..=0x7e) => end + 1,
+                _ => end,
+            }
+        }
+        _ => 1,
+    }
+}
+
+fn is_sgr(sequence: &str) -> bool {
+    sequence
+        .strip_prefix("\x1b[")
+        .and_then(|s| s.strip_suffix('m'))
+        .is_some_and(|params| params.bytes().all(|b| b.is_ascii_digit() || b == b';'))
+}
+
+fn push_escaped(out: &mut String, c: char) {
+    match c {
+        '\0'..='\x1f' => {
+            out.push('^');
+            out.push((c as u8 + 0x40) as char);
+        }
+        '\x7f' => out.push_str("^?"),
+        _ => out.push_str(&format!("<U+{:04X}>", c as u32)),
+    }
+}
+
+fn sanitize_str<'s>(text: Cow<'s, str>, policy: &Policy, preformatted: bool) -> Cow<'s, str> {
+    let is_unsafe = |c: char| is_unsafe(c) && !(preformatted && c == '\n');
+    if !text.contains(is_unsafe) {
+        return text;
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text.as_ref();
+    while let Some(index) = rest.find(is_unsafe) {
+        out.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        let c = rest.chars().next().expect("index is at a character");
+        let len = if c == ESC { escape_sequence_len(rest) } else { c.len_utf8() };
+        if c == ESC && preformatted && policy.preformatted_sgr && is_sgr(&rest[..len]) {
+            out.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+
+        match policy.action {
+            Action::Strip => rest = &rest[len..],
+            Action::Escape => {
+                // The rest of an escape sequence is printable, so only the ESC is escaped.
+                push_escaped(&mut out, c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/// Sanitises a string of text according to `policy`.
+pub fn sanitize_text<'s>(text: Cow<'s, str>, policy: &Policy) -> Cow<'s, str> {
+    sanitize_str(text, policy, false)
+}
+
+/// Sanitises all the text in `line` according to `policy`.
+pub fn sanitize_line<'s>(line: RawLine<'s>, policy: &Policy) -> RawLine<'s> {
+    let text = |s| sanitize_str(s, policy, false);
+    match line {
+        RawLine::Text(s) => RawLine::Text(text(s)),
+        RawLine::Heading(Heading::H1(s)) => RawLine::Heading(Heading::H1(text(s))),
+        RawLine::Heading(Heading::H2(s)) => RawLine::Heading(Heading::H2(text(s))),
+        RawLine::Heading(Heading::H3(s)) => RawLine::Heading(Heading::H3(text(s))),
+        RawLine::Link(link) => RawLine::Link(link.map_strs(text)),
+        RawLine::ListItem(s) => RawLine::ListItem(text(s)),
+        RawLine::Blockquote(s) => RawLine::Blockquote(text(s)),
+        RawLine::Preformatted(pre) => RawLine::Preformatted(Preformatted {
+            alt_text: pre.alt_text.map(text),
+            text: sanitize_str(pre.text, policy, true),
+        }),
+    }
+}
+
+/// Sanitises every line in `lines` according to `policy`.
+pub fn sanitize_lines<'s>(lines: Vec<RawLine<'s>>, policy: &Policy) -> Vec<RawLine<'s>> {
+    lines.into_iter().map(|line| sanitize_line(line, policy)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemtext::parse_lines;
+    use pretty_assertions::assert_eq;
+    use proptest::prelude::*;
+    use test_case::test_case;
+
+    const ESCAPE: Policy = Policy { action: Action::Escape, preformatted_sgr: true };
+    const STRIP: Policy = Policy { action: Action::Strip, preformatted_sgr: true };
+    const STRIP_ALL: Policy = Policy { action: Action::Strip, preformatted_sgr: false };
+
+    #[test_case("plain\ttext", STRIP, "plain\ttext" ; "safe text is unchanged")]
+    #[test_case("\x1b[31mred\x1b[0m", STRIP, "red" ; "csi sequences")]
+    #[test_case("\x1b]0;title\x07text", STRIP, "text" ; "osc terminated by bel")]
+    #[test_case("\x1b]8;;http://evil\x1b\\link", STRIP, "link" ; "osc terminated by st")]
+    #[test_case("\x1b]0;unterminated", STRIP, "" ; "unterminated osc")]
+    #[test_case("a\x1bcb\x1b(Bc", STRIP, "abc" ; "two character sequences")]
+    #[test_case("a\x1b", STRIP, "a" ; "lone esc")]
+    #[test_case("a\rb\x00c\x7fd\u{9b}e", STRIP, "abcde" ; "control characters")]
+    #[test_case("abc\u{202e}fed\u{2066}", STRIP, "abcfed" ; "bidi overrides")]
+    #[test_case("\x1b[31mred", ESCAPE, "^[[31mred" ; "escaped esc")]
+    #[test_case("a\rb\x7f\u{9b}\u{202e}", ESCAPE, "a^Mb^?<U+009B><U+202E>" ; "escaped control characters")]
+    fn test_sanitize_text(text: &str, policy: Policy, expected: &str) {
+        assert_eq!(sanitize_text(Cow::Borrowed(text), &policy), expected);
+    }
+
+    #[test_case(STRIP, "```\x1b[2J\nred: \x1b[1;31mx\x1b[m\r\n```\n", "red: \x1b[1;31mx\x1b[m" ; "sgr is kept")]
+    #[test_case(STRIP_ALL, "```\nred: \x1b[1;31mx\x1b[m\n```\n", "red: x" ; "sgr is stripped")]
+    #[test_case(STRIP, "```\nline\x1b[1A\nline\n```\n", "line\nline" ; "newlines are kept")]
+    fn test_sanitize_preformatted(policy: Policy, source: &str, expected: &str) {
+        let lines = sanitize_lines(parse_lines(source), &policy);
+        match &lines[..] {
+            [RawLine::Preformatted(pre)] => assert_eq!(pre.text, expected),
+            _ => panic!("expected a preformatted block, got {:?}", lines),
+        }
+    }
+
+    #[test]
+    fn test_sanitize_lines() {
+        let source = "# \x1b[5mHead\n=> rel\x1b[8m Te\u{202e}xt\n=> gemini://example.com/a\x1bb\n\
+                      * it\x1bcem\n> quo\x1b[2Jte\n```\x1b]0;x\x07alt\n```\n";
+        let expected = "# Head\n=> rel Text\n=> gemini://example.com/a%1Bb\n* item\n> quote\n```alt\n```\n";
+        let lines = sanitize_lines(parse_lines(source), &Policy::default());
+        assert_eq!(lines, parse_lines(expected));
+    }
+
+    #[test]
+    fn test_clean_text_is_borrowed() {
+        assert!(matches!(sanitize_text(Cow::Borrowed("clean"), &Policy::default()), Cow::Borrowed(_)));
+    }
+
+    proptest! {
+        #[test]
+        fn test_stripped_text_is_safe(text in any::<String>()) {
+            let sanitized = sanitize_text(Cow::Borrowed(&text), &STRIP_ALL);
+            prop_assert!(!sanitized.contains(is_unsafe));
+        }
+
+        #[test]
+        fn test_escaped_text_is_safe(text in any::<String>()) {
+            let sanitized = sanitize_text(Cow::Borrowed(&text), &ESCAPE);
+            prop_assert!(!sanitized.contains(is_unsafe));
+        }
+    }
+}
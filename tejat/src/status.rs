@@ -1,19 +1,47 @@
-use crate::mime::Mime;
+use crate::gemtext::Scheme;
+use crate::gemini_mime::GeminiMime;
 use crate::uri::GeminiUrl;
 use std::{fmt, ops::Deref, str::FromStr};
+use url::Url;
 
-#[derive(Debug, PartialEq, Eq)]
+/// The maximum length of a response header's meta, in bytes.
+pub const MAX_META_LENGTH: usize = 1024;
+
+/// The maximum length of a response header in bytes: two digits, a space, the meta and CRLF.
+pub const MAX_HEADER_LENGTH: usize = 2 + 1 + MAX_META_LENGTH + 2;
+
+/// The MIME type of a success response with an empty meta.
+pub const DEFAULT_MIME: &str = "text/gemini; charset=utf-8";
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
 pub enum Error {
     /// Expected a status code, found a non-number instead.
+    #[error("invalid status code {0:?}")]
     InvalidCode(String),
     /// Expected a valid status code, found an unknown number instead.
+    #[error("unknown status code {0}")]
     UnknownCode(u8),
     /// Expected a valid status code, found one for a different status type.
+    #[error("status code {0} is not valid for this status type")]
     WrongCodeForStatus(u8),
     /// Not a valid status string.
+    #[error("invalid status {0:?}")]
     InvalidStatus(String),
     /// Not a valid meta string for this status code.
+    #[error("invalid meta {0:?}")]
     InvalidMeta(String),
+    /// The header's meta is longer than [`MAX_META_LENGTH`] bytes.
+    #[error("meta is longer than {MAX_META_LENGTH} bytes")]
+    MetaTooLong,
+    /// The input ends before the CRLF that ends the header, so more of it has to be read.
+    #[error("header is incomplete")]
+    Incomplete,
+    /// The header ends with a LF that is not preceded by a CR.
+    #[error("header ends with LF instead of CRLF")]
+    BareLf,
+    /// The header is not valid UTF-8.
+    #[error("header is not valid UTF-8")]
+    InvalidUtf8,
 }
 
 impl From<std::convert::Infallible> for Error {
@@ -23,6 +51,11 @@ impl From<std::convert::Infallible> for Error {
 }
 
 #[derive(Copy, Clone, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "u8", try_from = "u8"),
+)]
 #[repr(transparent)]
 pub struct Code(u8);
 
@@ -68,18 +101,21 @@ impl FromStr for Code {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Status codes are always two digits.
-        // This disallows any shenanigans with signs and such.
-        if s.len() != 2 {
-            return Err(Error::InvalidCode(s.to_string()))
-        }
-
-        s.parse::<u8>().map(Code::try_from).map_err(|_| {
-            Error::InvalidCode(s.to_string())
-        })?
+        Code::try_from(Code::parse_number(s)?)
     }
 }
 
+/// The kind of response, given by the first digit of its status code.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum StatusCategory {
+    Input,
+    Success,
+    Redirect,
+    TemporaryFailure,
+    PermanentFailure,
+    ClientCertificateRequired,
+}
+
 impl Code {
     pub const INPUT: Self = Self(10);
     pub const SENSITIVE_INPUT: Self = Self(11);
@@ -125,15 +161,74 @@ impl Code {
         let code = Code(code);
         Code::ALL_CODES.binary_search(&code).is_ok()
     }
+
+    /// Parses the two digits of a status code, which need not be a known code.
+    fn parse_number(s: &str) -> Result<u8, Error> {
+        // Status codes are always two digits.
+        // This disallows any shenanigans with signs and such.
+        if s.len() != 2 || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(Error::InvalidCode(s.to_string()))
+        }
+
+        s.parse::<u8>().map_err(|_| Error::InvalidCode(s.to_string()))
+    }
+
+    /// Like [`Code::try_from`], but maps unknown codes in a known category to the category's
+    /// base code (e.g. `45` to `40`), as the spec tells clients to.
+    pub fn from_u8_lenient(value: u8) -> Result<Self, Error> {
+        match Self::try_from(value) {
+            Err(_) if (10..70).contains(&value) => Ok(Self(value / 10 * 10)),
+            result => result,
+        }
+    }
+
+    pub fn category(&self) -> StatusCategory {
+        match self.0 / 10 {
+            1 => StatusCategory::Input,
+            2 => StatusCategory::Success,
+            3 => StatusCategory::Redirect,
+            4 => StatusCategory::TemporaryFailure,
+            5 => StatusCategory::PermanentFailure,
+            6 => StatusCategory::ClientCertificateRequired,
+            _ => unreachable!("codes are always valid"),
+        }
+    }
 }
 
 macro_rules! impl_code {
     ($name: ident { $($code: expr => $variant: ident { $meta: ident : $meta_type: ty },)* }) => {
         #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(into = "StatusRepr", try_from = "StatusRepr"),
+        )]
         pub enum $name {
             $($variant { $meta: $meta_type },)*
         }
 
+        #[cfg(feature = "serde")]
+        impl From<$name> for StatusRepr {
+            fn from(status: $name) -> Self {
+                match status {
+                    $($name::$variant { $meta } => StatusRepr { code: $code, meta: $meta.to_string() },)*
+                }
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl TryFrom<StatusRepr> for $name {
+            type Error = Error;
+
+            /// Accepts the same input as a [`Status`] would, as long as the code belongs here.
+            fn try_from(repr: StatusRepr) -> Result<Self, Self::Error> {
+                match Status::from_header_parts(repr.code, &repr.meta)? {
+                    Status::$name(status) => Ok(status),
+                    status => Err(Error::WrongCodeForStatus(*status.code())),
+                }
+            }
+        }
+
         impl fmt::Display for $name {
             fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 match self {
@@ -150,6 +245,7 @@ macro_rules! impl_code {
                 let (code_str, meta) = s.split_once(' ')
                     .ok_or_else(|| Error::InvalidStatus(s.to_string()))?;
                 let code: Code = code_str.parse()?;
+                validate_meta(meta)?;
                 $(
                 if code == $code {
                     let $meta = meta.parse::<$meta_type>().map_err(|_| Error::InvalidMeta(meta.to_string()))?;
@@ -191,32 +287,100 @@ impl Input {
 
 impl_code! {
     Success {
-        Code::SUCCESS => Normal { mime: Mime },
+        Code::SUCCESS => Normal { mime: GeminiMime },
     }
 }
 
 impl Success {
-    pub fn mime(&self) -> &Mime {
+    pub fn mime(&self) -> &GeminiMime {
         match self {
             Self::Normal { mime } => mime,
         }
     }
 }
 
+/// Where a redirect points to.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum RedirectTarget {
+    /// An absolute URL, which may use a scheme other than `gemini`.
+    Absolute(Url),
+    /// A relative reference, to be resolved against the URL of the request.
+    Relative(String),
+}
+
+impl RedirectTarget {
+    /// The scheme of an absolute target. Relative targets have the scheme of the request.
+    pub fn scheme(&self) -> Option<Scheme> {
+        match self {
+            Self::Absolute(url) => Some(Scheme::from(url.scheme())),
+            Self::Relative(_) => None,
+        }
+    }
+
+    /// Checks whether following the redirect from a gemini URL stays on Gemini. Clients should
+    /// ask before following redirects to other protocols.
+    pub fn is_gemini(&self) -> bool {
+        matches!(self.scheme(), None | Some(Scheme::Gemini))
+    }
+}
+
+impl fmt::Display for RedirectTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Absolute(url) => write!(f, "{}", url),
+            Self::Relative(reference) => f.write_str(reference),
+        }
+    }
+}
+
+impl FromStr for RedirectTarget {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Url::parse(s) {
+            Ok(url) => Ok(Self::Absolute(url)),
+            Err(url::ParseError::RelativeUrlWithoutBase) if !s.is_empty() && !s.contains(char::is_whitespace) => {
+                Ok(Self::Relative(s.to_string()))
+            }
+            Err(_) => Err(Error::InvalidMeta(s.to_string())),
+        }
+    }
+}
+
+impl From<Url> for RedirectTarget {
+    fn from(url: Url) -> Self {
+        Self::Absolute(url)
+    }
+}
+
+impl From<GeminiUrl> for RedirectTarget {
+    fn from(url: GeminiUrl) -> Self {
+        Self::Absolute(url.as_url().clone())
+    }
+}
+
 impl_code! {
     Redirect {
-        Code::TEMPORARY_REDIRECT => Temporary { target: GeminiUrl },
-        Code::PERMANENT_REDIRECT => Permanent { target: GeminiUrl },
+        Code::TEMPORARY_REDIRECT => Temporary { target: RedirectTarget },
+        Code::PERMANENT_REDIRECT => Permanent { target: RedirectTarget },
     }
 }
 
 impl Redirect {
-    pub fn target(&self) -> &GeminiUrl {
+    pub fn target(&self) -> &RedirectTarget {
         match self {
             Self::Temporary { target } | Self::Permanent { target } => target
         }
     }
 
+    /// Resolves the target against `base`, the URL that was requested.
+    pub fn resolve(&self, base: &GeminiUrl) -> Result<Url, url::ParseError> {
+        match self.target() {
+            RedirectTarget::Absolute(url) => Ok(url.clone()),
+            RedirectTarget::Relative(reference) => base.as_url().join(reference),
+        }
+    }
+
     pub fn is_temporary(&self) -> bool {
         matches!(self, Self::Temporary { .. })
     }
@@ -254,7 +418,18 @@ impl_code! {
     }
 }
 
+/// A response status.
+///
+/// # Serialization
+///
+/// With the `serde` feature, a status and each of the per-category enums are serialized as
+/// `{"code": 51, "meta": "Not found"}`, with the meta as it is sent in the header.
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "StatusRepr", try_from = "StatusRepr"),
+)]
 pub enum Status {
     Input(Input),
     Success(Success),
@@ -264,6 +439,37 @@ pub enum Status {
     ClientCertificateRequired(ClientCertificateRequired),
 }
 
+/// A status as serialized.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StatusRepr {
+    code: Code,
+    meta: String,
+}
+
+#[cfg(feature = "serde")]
+impl From<Status> for StatusRepr {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::Input(inner) => inner.into(),
+            Status::Success(inner) => inner.into(),
+            Status::Redirect(inner) => inner.into(),
+            Status::TemporaryFailure(inner) => inner.into(),
+            Status::PermanentFailure(inner) => inner.into(),
+            Status::ClientCertificateRequired(inner) => inner.into(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<StatusRepr> for Status {
+    type Error = Error;
+
+    fn try_from(repr: StatusRepr) -> Result<Self, Self::Error> {
+        Self::from_header_parts(repr.code, &repr.meta)
+    }
+}
+
 impl fmt::Display for Status {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -299,7 +505,117 @@ impl FromStr for Status {
     }
 }
 
+/// A status parsed leniently, along with the code that was actually received.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct LenientStatus {
+    pub status: Status,
+    /// The received code, which is different from the status' code if it was unknown.
+    pub received_code: u8,
+}
+
+impl LenientStatus {
+    /// Checks whether the received code was unknown and mapped to its category's base code.
+    pub fn is_fallback(&self) -> bool {
+        self.received_code != *self.status.code()
+    }
+}
+
 impl Status {
+    /// Splits the response header at the start of `bytes` into the code, the meta and the
+    /// length of the header.
+    fn split_header(bytes: &[u8]) -> Result<(&str, &str, usize), Error> {
+        let limit = bytes.len().min(MAX_HEADER_LENGTH);
+        let Some(lf) = bytes[..limit].iter().position(|b| *b == b'\n') else {
+            return Err(if limit == MAX_HEADER_LENGTH { Error::MetaTooLong } else { Error::Incomplete });
+        };
+        let Some(end) = lf.checked_sub(1).filter(|cr| bytes[*cr] == b'\r') else {
+            return Err(Error::BareLf);
+        };
+
+        let header = std::str::from_utf8(&bytes[..end]).map_err(|_| Error::InvalidUtf8)?;
+        let (code_str, meta) = match header.get(2..) {
+            Some("") => (header, ""),
+            Some(rest) => match rest.strip_prefix(' ') {
+                Some(meta) => (&header[..2], meta),
+                None => return Err(Error::InvalidStatus(header.to_string())),
+            },
+            None => return Err(Error::InvalidCode(header.to_string())),
+        };
+        if meta.len() > MAX_META_LENGTH {
+            return Err(Error::MetaTooLong);
+        }
+        Ok((code_str, meta, end + 2))
+    }
+
+    fn from_header_parts(code: Code, meta: &str) -> Result<Self, Error> {
+        let meta = if code == Code::SUCCESS && meta.is_empty() { DEFAULT_MIME } else { meta };
+        format!("{} {}", code, meta).parse()
+    }
+
+    /// Parses the response header at the start of `bytes`, e.g. as read from a socket.
+    ///
+    /// Returns the status and the number of bytes of the header, including the CRLF, so the
+    /// rest of `bytes` is the start of the body. A success header with an empty meta (`20\r\n`)
+    /// has the MIME type [`DEFAULT_MIME`].
+    pub fn parse_header(bytes: &[u8]) -> Result<(Self, usize), Error> {
+        let (code_str, meta, len) = Self::split_header(bytes)?;
+        let status = Self::from_header_parts(code_str.parse()?, meta)?;
+        Ok((status, len))
+    }
+
+    /// Like [`Status::parse_header`], but unknown codes are handled like
+    /// [`Status::parse_lenient`] does.
+    pub fn parse_header_lenient(bytes: &[u8]) -> Result<(LenientStatus, usize), Error> {
+        let (code_str, meta, len) = Self::split_header(bytes)?;
+        let received_code = Code::parse_number(code_str)?;
+        let status = Self::from_header_parts(Code::from_u8_lenient(received_code)?, meta)?;
+        Ok((LenientStatus { status, received_code }, len))
+    }
+
+    /// Parses a status line like [`FromStr`], but treats unknown codes as the base code of
+    /// their category, e.g. `45` as `40` (temporary failure). Codes outside of the known
+    /// categories are still rejected.
+    pub fn parse_lenient(s: &str) -> Result<LenientStatus, Error> {
+        let (code_str, meta) = s.split_once(' ').ok_or_else(|| Error::InvalidStatus(s.to_string()))?;
+        let received_code = Code::parse_number(code_str)?;
+        let code = Code::from_u8_lenient(received_code)?;
+        let status = format!("{} {}", code, meta).parse()?;
+        Ok(LenientStatus { status, received_code })
+    }
+
+    pub fn category(&self) -> StatusCategory {
+        self.code().category()
+    }
+
+    pub fn is_input(&self) -> bool {
+        matches!(self, Self::Input(_))
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Success(_))
+    }
+
+    pub fn is_redirect(&self) -> bool {
+        matches!(self, Self::Redirect(_))
+    }
+
+    pub fn is_temporary_failure(&self) -> bool {
+        matches!(self, Self::TemporaryFailure(_))
+    }
+
+    pub fn is_permanent_failure(&self) -> bool {
+        matches!(self, Self::PermanentFailure(_))
+    }
+
+    /// Checks whether this is a temporary or permanent failure.
+    pub fn is_failure(&self) -> bool {
+        self.is_temporary_failure() || self.is_permanent_failure()
+    }
+
+    pub fn is_client_certificate_required(&self) -> bool {
+        matches!(self, Self::ClientCertificateRequired(_))
+    }
+
     pub fn code(&self) -> Code {
         match self {
             Self::Input(inner) => inner.code(),
@@ -310,6 +626,87 @@ impl Status {
             Self::ClientCertificateRequired(inner) => inner.code(),
         }
     }
+
+    /// Checks that the status can be sent as a header, like the validating constructors do for
+    /// statuses built by hand.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let header = self.to_string();
+        let meta = header.strip_suffix("\r\n")
+            .and_then(|header| header.split_once(' '))
+            .map_or("", |(_, meta)| meta);
+        validate_meta(meta)
+    }
+}
+
+/// Checks that `meta` can be sent in a header: it must fit in [`MAX_META_LENGTH`] bytes and not
+/// contain a CR or LF, which would end the header early.
+fn validate_meta(meta: &str) -> Result<(), Error> {
+    if meta.contains(['\r', '\n']) {
+        Err(Error::InvalidMeta(meta.to_string()))
+    } else if meta.len() > MAX_META_LENGTH {
+        Err(Error::MetaTooLong)
+    } else {
+        Ok(())
+    }
+}
+
+macro_rules! message_constructors {
+    ($($fn_name: ident => $status: ident :: $variant: ident { $meta: ident },)*) => {
+        $(
+        #[doc = concat!("Creates a `", stringify!($status), "::", stringify!($variant), "` status, ")]
+        #[doc = "failing if the text cannot be sent in a header."]
+        pub fn $fn_name<S: Into<String>>($meta: S) -> Result<Self, Error> {
+            let $meta = $meta.into();
+            validate_meta(&$meta)?;
+            Ok(Self::$status($status::$variant { $meta }))
+        }
+        )*
+    }
+}
+
+/// Constructors that validate the meta, so that the status can be sent as is.
+impl Status {
+    message_constructors! {
+        input => Input::Normal { prompt },
+        sensitive_input => Input::Sensitive { prompt },
+        temporary_failure => TemporaryFailure::Generic { message },
+        server_unavailable => TemporaryFailure::ServerUnavailable { message },
+        cgi_error => TemporaryFailure::CgiError { message },
+        proxy_error => TemporaryFailure::ProxyError { message },
+        permanent_failure => PermanentFailure::Generic { message },
+        not_found => PermanentFailure::NotFound { message },
+        gone => PermanentFailure::Gone { message },
+        proxy_request_refused => PermanentFailure::ProxyRequestRefused { message },
+        bad_request => PermanentFailure::BadRequest { message },
+        client_certificate_required => ClientCertificateRequired::Required { message },
+        certificate_not_authorised => ClientCertificateRequired::NotAuthorised { message },
+        certificate_not_valid => ClientCertificateRequired::NotValid { message },
+    }
+
+    /// Creates a success status for a body of type `mime`.
+    pub fn success(mime: GeminiMime) -> Result<Self, Error> {
+        validate_meta(&mime.to_string())?;
+        Ok(Self::Success(Success::Normal { mime }))
+    }
+
+    /// Creates a temporary redirect to `target`.
+    pub fn redirect_temporary<T: Into<RedirectTarget>>(target: T) -> Result<Self, Error> {
+        let target = target.into();
+        validate_meta(&target.to_string())?;
+        Ok(Self::Redirect(Redirect::Temporary { target }))
+    }
+
+    /// Creates a permanent redirect to `target`.
+    pub fn redirect_permanent<T: Into<RedirectTarget>>(target: T) -> Result<Self, Error> {
+        let target = target.into();
+        validate_meta(&target.to_string())?;
+        Ok(Self::Redirect(Redirect::Permanent { target }))
+    }
+
+    /// Asks the client to wait `wait_secs` seconds before sending another request.
+    pub fn slow_down(wait_secs: u16) -> Self {
+        Self::TemporaryFailure(TemporaryFailure::SlowDown { wait_secs })
+    }
 }
 
 #[cfg(test)]
@@ -567,4 +964,214 @@ mod tests {
             "20 Success is not an input status" => Error::WrongCodeForStatus(20)
         }
     }
+
+    mod parse_header {
+        use super::*;
+        use test_case::test_case;
+
+        #[test_case(b"20 text/plain\r\nbody", Status::Success(Success::Normal { mime: "text/plain".parse().unwrap() }), 15 ; "success")]
+        #[test_case(b"20\r\n", Status::Success(Success::Normal { mime: DEFAULT_MIME.parse().unwrap() }), 4 ; "empty success meta")]
+        #[test_case(b"20 \r\n", Status::Success(Success::Normal { mime: DEFAULT_MIME.parse().unwrap() }), 5 ; "empty success meta after space")]
+        #[test_case(b"51\r\n", Status::PermanentFailure(PermanentFailure::NotFound { message: String::new() }), 4 ; "empty failure meta")]
+        #[test_case(b"10 Name?\r\n", Status::Input(Input::Normal { prompt: String::from("Name?") }), 10 ; "input")]
+        fn test_valid(bytes: &[u8], expected: Status, consumed: usize) {
+            assert_eq!(Status::parse_header(bytes), Ok((expected, consumed)));
+        }
+
+        #[test_case(b"20 text/gemini", Error::Incomplete ; "incomplete")]
+        #[test_case(b"20 text/gemini\r", Error::Incomplete ; "cr without lf")]
+        #[test_case(b"20 text/gemini\n", Error::BareLf ; "bare lf")]
+        #[test_case(b"20 text/gemini\nbody\r\n", Error::BareLf ; "bare lf before crlf")]
+        #[test_case(b"51 \xff\r\n", Error::InvalidUtf8 ; "invalid utf8")]
+        #[test_case(b"2\r\n", Error::InvalidCode(String::from("2")) ; "one digit")]
+        #[test_case(b"200 text/gemini\r\n", Error::InvalidStatus(String::from("200 text/gemini")) ; "three digits")]
+        #[test_case(b"20\ttext/gemini\r\n", Error::InvalidStatus(String::from("20\ttext/gemini")) ; "no space")]
+        #[test_case(b"12 prompt\r\n", Error::UnknownCode(12) ; "unknown code")]
+        fn test_invalid(bytes: &[u8], expected: Error) {
+            assert_eq!(Status::parse_header(bytes), Err(expected));
+        }
+
+        #[test]
+        fn test_meta_length() {
+            let longest = format!("51 {}\r\n", "a".repeat(MAX_META_LENGTH));
+            assert!(Status::parse_header(longest.as_bytes()).is_ok());
+
+            let too_long = format!("51 {}\r\n", "a".repeat(MAX_META_LENGTH + 1));
+            assert_eq!(Status::parse_header(too_long.as_bytes()), Err(Error::MetaTooLong));
+            assert_eq!(Status::parse_header(&too_long.as_bytes()[..1028]), Err(Error::Incomplete));
+            assert_eq!(Status::parse_header(&too_long.as_bytes()[..1029]), Err(Error::MetaTooLong));
+        }
+    }
+
+    mod lenient {
+        use super::*;
+        use test_case::test_case;
+
+        #[test_case("51 Not found", Status::PermanentFailure(PermanentFailure::NotFound { message: String::from("Not found") }), 51 ; "known code")]
+        #[test_case("45 Busy\r\n", Status::TemporaryFailure(TemporaryFailure::Generic { message: String::from("Busy") }), 45 ; "unknown temporary failure")]
+        #[test_case("57 Nope", Status::PermanentFailure(PermanentFailure::Generic { message: String::from("Nope") }), 57 ; "unknown permanent failure")]
+        #[test_case("12 Name?", Status::Input(Input::Normal { prompt: String::from("Name?") }), 12 ; "unknown input")]
+        #[test_case("21 text/plain", Status::Success(Success::Normal { mime: "text/plain".parse().unwrap() }), 21 ; "unknown success")]
+        fn test_parse_lenient(s: &str, status: Status, received_code: u8) {
+            let lenient = Status::parse_lenient(s).unwrap();
+            assert_eq!(lenient.is_fallback(), received_code != *status.code());
+            assert_eq!(lenient, LenientStatus { status, received_code });
+        }
+
+        #[test_case("70 Unknown category", Error::UnknownCode(70) ; "unknown category")]
+        #[test_case("05 Unknown category", Error::UnknownCode(5) ; "leading zero")]
+        #[test_case("+5 Sign", Error::InvalidCode(String::from("+5")) ; "sign")]
+        #[test_case("45", Error::InvalidStatus(String::from("45")) ; "no meta")]
+        fn test_parse_lenient_invalid(s: &str, expected: Error) {
+            assert_eq!(Status::parse_lenient(s), Err(expected));
+        }
+
+        #[test]
+        fn test_strict_parsing_rejects_unknown_codes() {
+            assert_eq!("45 Busy".parse::<Status>(), Err(Error::UnknownCode(45)));
+            assert_eq!(Status::parse_header(b"45 Busy\r\n"), Err(Error::UnknownCode(45)));
+        }
+
+        #[test]
+        fn test_parse_header_lenient() {
+            let (lenient, len) = Status::parse_header_lenient(b"29\r\nbody").unwrap();
+            assert_eq!(lenient.status, Status::Success(Success::Normal { mime: DEFAULT_MIME.parse().unwrap() }));
+            assert_eq!(lenient.received_code, 29);
+            assert_eq!(len, 4);
+        }
+
+        #[test]
+        fn test_categories() {
+            let categories = [
+                (Code::INPUT, StatusCategory::Input),
+                (Code::SENSITIVE_INPUT, StatusCategory::Input),
+                (Code::SUCCESS, StatusCategory::Success),
+                (Code::PERMANENT_REDIRECT, StatusCategory::Redirect),
+                (Code::SLOW_DOWN, StatusCategory::TemporaryFailure),
+                (Code::BAD_REQUEST, StatusCategory::PermanentFailure),
+                (Code::CERTIFICATE_NOT_VALID, StatusCategory::ClientCertificateRequired),
+            ];
+            for (code, category) in categories {
+                assert_eq!(code.category(), category, "category of {}", code);
+            }
+        }
+
+        #[test]
+        fn test_predicates() {
+            let status: Status = "44 60".parse().unwrap();
+            assert_eq!(status.category(), StatusCategory::TemporaryFailure);
+            assert!(status.is_temporary_failure());
+            assert!(status.is_failure());
+            assert!(!status.is_permanent_failure());
+            assert!(!status.is_success());
+
+            let status: Status = "20 text/gemini".parse().unwrap();
+            assert!(status.is_success());
+            assert!(!status.is_failure());
+            assert!(!status.is_input());
+            assert!(!status.is_redirect());
+            assert!(!status.is_client_certificate_required());
+        }
+    }
+
+    mod redirect {
+        use super::*;
+        use test_case::test_case;
+
+        #[test_case("30 gemini://example.com/new\r\n", "gemini://example.com/new", true ; "absolute")]
+        #[test_case("31 /new/path\r\n", "gemini://example.com/new/path", true ; "root relative")]
+        #[test_case("31 ../up", "gemini://example.com/up", true ; "relative")]
+        #[test_case("30 https://example.com/", "https://example.com/", false ; "other protocol")]
+        fn test_resolve(header: &str, expected: &str, is_gemini: bool) {
+            let base: GeminiUrl = "gemini://example.com/dir/page.gmi".parse().unwrap();
+            let Status::Redirect(redirect) = header.parse::<Status>().unwrap() else {
+                panic!("expected a redirect");
+            };
+            assert_eq!(redirect.resolve(&base).unwrap().as_str(), expected);
+            assert_eq!(redirect.target().is_gemini(), is_gemini);
+            assert_eq!(redirect.to_string(), header.trim_end());
+        }
+
+        #[test_case("31 " ; "empty")]
+        #[test_case("31 two words" ; "whitespace")]
+        #[test_case("31 gemini://[::1" ; "invalid url")]
+        fn test_invalid_target(header: &str) {
+            assert!(matches!(header.parse::<Redirect>(), Err(Error::InvalidMeta(_))));
+        }
+
+        #[test]
+        fn test_scheme() {
+            let target: RedirectTarget = "spartan://example.com/".parse().unwrap();
+            assert_eq!(target.scheme(), Some(Scheme::Spartan));
+            assert!(!target.is_gemini());
+            assert_eq!("/relative".parse::<RedirectTarget>().unwrap().scheme(), None);
+        }
+    }
+
+    mod success {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn test_lang() {
+            let status = Status::Success(Success::Normal { mime: GeminiMime::gemtext().with_lang(["en", "fr"]).unwrap() });
+            assert_eq!(status.to_string(), "20 text/gemini; lang=en,fr\r\n");
+
+            let (parsed, _) = Status::parse_header(b"20 text/gemini; lang=en,fr\r\n").unwrap();
+            assert_eq!(parsed, status);
+            let Status::Success(success) = parsed else {
+                panic!("expected a success");
+            };
+            assert_eq!(success.mime().lang(), ["en", "fr"]);
+        }
+    }
+
+    mod constructors {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use test_case::test_case;
+
+        #[test_case(Status::input("Name?").unwrap(), "10 Name?" ; "input")]
+        #[test_case(Status::sensitive_input("Password?").unwrap(), "11 Password?" ; "sensitive input")]
+        #[test_case(Status::success(GeminiMime::gemtext()).unwrap(), "20 text/gemini" ; "success")]
+        #[test_case(Status::redirect_temporary(Url::parse("gemini://example.com/").unwrap()).unwrap(), "30 gemini://example.com/" ; "temporary redirect")]
+        #[test_case(Status::redirect_permanent(RedirectTarget::Relative(String::from("/new"))).unwrap(), "31 /new" ; "permanent redirect")]
+        #[test_case(Status::server_unavailable("Maintenance").unwrap(), "41 Maintenance" ; "server unavailable")]
+        #[test_case(Status::slow_down(30), "44 30" ; "slow down")]
+        #[test_case(Status::not_found("Not found").unwrap(), "51 Not found" ; "not found")]
+        #[test_case(Status::bad_request("").unwrap(), "59 " ; "empty message")]
+        #[test_case(Status::certificate_not_valid("Expired").unwrap(), "62 Expired" ; "certificate not valid")]
+        fn test_valid(status: Status, expected: &str) {
+            assert_eq!(status.to_string(), format!("{}\r\n", expected));
+            assert_eq!(expected.parse::<Status>().unwrap(), status);
+        }
+
+        #[test]
+        fn test_invalid() {
+            assert_eq!(Status::not_found("a\r\n20 text/html"), Err(Error::InvalidMeta(String::from("a\r\n20 text/html"))));
+            assert_eq!(Status::input("a\nb"), Err(Error::InvalidMeta(String::from("a\nb"))));
+            assert_eq!(Status::gone("a".repeat(MAX_META_LENGTH + 1)), Err(Error::MetaTooLong));
+            assert!(Status::gone("a".repeat(MAX_META_LENGTH)).is_ok());
+            assert_eq!(
+                Status::redirect_permanent(RedirectTarget::Relative(format!("/{}", "a".repeat(MAX_META_LENGTH)))),
+                Err(Error::MetaTooLong)
+            );
+        }
+
+        #[test]
+        fn test_parse_validates_meta() {
+            assert_eq!("51 a\r\n20 text/html".parse::<Status>(), Err(Error::InvalidMeta(String::from("a\r\n20 text/html"))));
+            assert_eq!("51 a\rb".parse::<PermanentFailure>(), Err(Error::InvalidMeta(String::from("a\rb"))));
+            assert_eq!(format!("51 {}", "a".repeat(MAX_META_LENGTH + 1)).parse::<Status>(), Err(Error::MetaTooLong));
+        }
+
+        #[test]
+        fn test_error_trait() {
+            fn parse(s: &str) -> Result<Status, Box<dyn std::error::Error>> {
+                Ok(s.parse()?)
+            }
+            assert_eq!(parse("99 Nope").unwrap_err().to_string(), "unknown status code 99");
+            assert_eq!(Error::MetaTooLong.to_string(), "meta is longer than 1024 bytes");
+        }
+    }
 }
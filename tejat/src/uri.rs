@@ -1,15 +1,77 @@
-use std::{str::FromStr, fmt};
-use url::Url;
+use std::{borrow::Cow, str::FromStr, fmt};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use url::{Host, Position, Url};
 
 const SCHEME_GEMINI: &str = "gemini";
 
+/// The port Gemini servers listen on unless a URL says otherwise.
+pub const DEFAULT_PORT: u16 = 1965;
+
+/// Everything but unreserved characters, so that spaces become `%20` and not `+`.
+const INPUT_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// Uppercases the hex digits of percent-encoded bytes and decodes the ones that are unreserved
+/// characters, as described in [RFC 3986, section 6.2.2](https://www.rfc-editor.org/rfc/rfc3986#section-6.2.2).
+fn normalize_percent_encoding(s: &str) -> String {
+    let mut normalized = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(index) = rest.find('%') {
+        normalized.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+
+        let hex = rest.get(..2).filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()));
+        let Some(hex) = hex else {
+            normalized.push('%');
+            continue;
+        };
+        let byte = u8::from_str_radix(hex, 16).expect("hex digits were checked");
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            normalized.push(byte as char);
+        } else {
+            normalized.push('%');
+            normalized.push_str(&hex.to_ascii_uppercase());
+        }
+        rest = &rest[2..];
+    }
+    normalized.push_str(rest);
+    normalized
+}
+
+/// The maximum length of a URL in a request, in bytes.
+pub const MAX_URL_LENGTH: usize = 1024;
+
+/// The length of `url` as it is sent in a request, i.e. without its fragment.
+fn request_length(url: &Url) -> usize {
+    url[..Position::AfterQuery].len()
+}
+
+#[derive(Debug, Eq, PartialEq, thiserror::Error)]
 pub enum UrlParseError {
+    #[error("invalid URL: {0}")]
     InvalidUrl(url::ParseError),
-    /// Contains the non-gemini scheme as [`String`].
+    /// Contains the URL with the non-gemini scheme.
+    #[error("not a gemini URL: {0}")]
     NotGeminiScheme(Url),
+    /// The URL, without its fragment, is longer than [`MAX_URL_LENGTH`] bytes. Contains its length.
+    #[error("URL is {0} bytes long, but may be at most {MAX_URL_LENGTH}")]
+    TooLong(usize),
+    #[error("URL contains a username or password: {0}")]
     HasUserInfo(Url),
+    /// The URL has no authority component, like `gemini:path`.
+    #[error("URL has no host: {0}")]
     MissingAuthority(Url),
-    MissingHost(Url),
+    /// The URL has an authority, but the host is empty, like `gemini:///path`. Contains the URL.
+    #[error("URL has an empty host: {0}")]
+    EmptyHost(String),
+}
+
+/// Returned by [`GeminiUrl::with_input`] when the URL with the input would be too long to request.
+#[derive(Debug, Eq, PartialEq, thiserror::Error)]
+#[error("URL with input is {over} bytes longer than the maximum of {MAX_URL_LENGTH}")]
+pub struct InputTooLong {
+    /// How many bytes the URL is over [`MAX_URL_LENGTH`].
+    pub over: usize,
 }
 
 impl From<url::ParseError> for UrlParseError {
@@ -21,16 +83,173 @@ impl From<url::ParseError> for UrlParseError {
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct GeminiUrl(Url);
 
+impl GeminiUrl {
+    pub fn as_url(&self) -> &Url {
+        &self.0
+    }
+
+    /// Returns this URL without its fragment, which is never sent in a request.
+    pub fn without_fragment(&self) -> GeminiUrl {
+        let mut url = self.0.clone();
+        url.set_fragment(None);
+        GeminiUrl(url)
+    }
+
+    /// The URL's port, or [`DEFAULT_PORT`] if it has none.
+    pub fn port_or_default(&self) -> u16 {
+        self.0.port().unwrap_or(DEFAULT_PORT)
+    }
+
+    /// Returns the URL in a normal form, so that URLs that refer to the same resource compare
+    /// equal.
+    ///
+    /// The host is lowercased and internationalised domain names are converted to punycode, the
+    /// default port is removed, an empty path becomes `/`, and percent-encoding in the path and
+    /// query is normalised. Dot segments are already removed when a URL is parsed.
+    pub fn normalized(&self) -> GeminiUrl {
+        let mut url = self.0.clone();
+
+        // `url` treats gemini hosts as opaque, so they have to be parsed as domains separately.
+        if let Some(host) = url.host_str() {
+            let host = match Host::parse(host) {
+                Ok(host) => host.to_string(),
+                Err(_) => host.to_ascii_lowercase(),
+            };
+            // Setting a host can only fail for URLs that cannot have one.
+            let _ = url.set_host(Some(&host));
+        }
+        if url.port() == Some(DEFAULT_PORT) {
+            let _ = url.set_port(None);
+        }
+
+        let path = normalize_percent_encoding(url.path());
+        url.set_path(if path.is_empty() { "/" } else { &path });
+        if let Some(query) = url.query().map(normalize_percent_encoding) {
+            url.set_query(Some(&query));
+        }
+
+        GeminiUrl(url)
+    }
+
+    /// Returns this URL with `input` as its query, to answer an
+    /// [`Input`](crate::status::Input) response.
+    ///
+    /// Any existing query is replaced. Everything in `input` other than unreserved characters is
+    /// percent-encoded, including spaces as `%20`.
+    pub fn with_input(&self, input: &str) -> Result<GeminiUrl, InputTooLong> {
+        let mut url = self.0.clone();
+        url.set_query(Some(&utf8_percent_encode(input, INPUT_ENCODE_SET).to_string()));
+        match request_length(&url).checked_sub(MAX_URL_LENGTH) {
+            Some(over) if over > 0 => Err(InputTooLong { over }),
+            _ => Ok(GeminiUrl(url)),
+        }
+    }
+
+    /// Returns the decoded query, i.e. the user's input in a request answering an
+    /// [`Input`](crate::status::Input) response.
+    ///
+    /// Invalid UTF-8 is replaced with `U+FFFD`. A `+` is kept as it is.
+    pub fn input(&self) -> Option<Cow<'_, str>> {
+        self.0.query().map(|query| percent_decode_str(query).decode_utf8_lossy())
+    }
+
+    /// Checks whether both URLs refer to the same resource, i.e. are equal after normalisation.
+    pub fn equivalent(&self, other: &GeminiUrl) -> bool {
+        self.normalized() == other.normalized()
+    }
+
+    fn same_origin(&self, other: &GeminiUrl) -> bool {
+        self.0.scheme() == other.0.scheme()
+            && self.0.host_str() == other.0.host_str()
+            && self.0.port() == other.0.port()
+    }
+
+    /// Appends the query and fragment, if any, to `reference`.
+    fn with_query_and_fragment(&self, mut reference: String) -> String {
+        if let Some(query) = self.0.query() {
+            reference.push('?');
+            reference.push_str(query);
+        }
+        if let Some(fragment) = self.0.fragment() {
+            reference.push('#');
+            reference.push_str(fragment);
+        }
+        reference
+    }
+
+    /// Returns the shortest relative reference that resolves to this URL from the page at `from`.
+    ///
+    /// Directories (paths ending in `/`) are kept as directories, so a link to the directory
+    /// `from` is in is `./`. `index.gmi` is treated like any other file, as it is only served
+    /// for its directory by convention. If the URLs have a different host or port, or this URL
+    /// has an empty path, the whole URL is returned.
+    pub fn make_relative(&self, from: &GeminiUrl) -> String {
+        if !self.same_origin(from) || self.0.path().is_empty() {
+            return self.0.to_string();
+        }
+
+        // The last segment of `from` is the page itself, which references are resolved next to.
+        let from_path = from.0.path();
+        let from_dirs: Vec<&str> = from_path[..from_path.rfind('/').unwrap_or(0)]
+            .split('/')
+            .skip(1)
+            .collect();
+        let segments: Vec<&str> = self.0.path().split('/').skip(1).collect();
+
+        let common = from_dirs.iter()
+            .zip(&segments[..segments.len() - 1])
+            .take_while(|(a, b)| a == b)
+            .count();
+        let mut reference = "../".repeat(from_dirs.len() - common);
+        reference.push_str(&segments[common..].join("/"));
+
+        // An empty reference would point to `from` itself, one starting with `/` would be
+        // root-relative, and a colon in the first segment would make it look like a scheme.
+        let first_segment = reference.split('/').next().unwrap_or_default();
+        if reference.is_empty() || reference.starts_with('/') || first_segment.contains(':') {
+            reference.insert_str(0, "./");
+        }
+        self.with_query_and_fragment(reference)
+    }
+
+    /// Returns a reference starting with `/` that resolves to this URL from the page at `from`.
+    ///
+    /// If the URLs have a different host or port, or this URL has an empty path, the whole URL
+    /// is returned.
+    pub fn make_root_relative(&self, from: &GeminiUrl) -> String {
+        if !self.same_origin(from) || self.0.path().is_empty() {
+            return self.0.to_string();
+        }
+
+        let path = self.0.path();
+        // A reference starting with `//` would be read as a host.
+        let reference = if path.starts_with("//") {
+            format!("/.{}", path)
+        } else {
+            path.to_string()
+        };
+        self.with_query_and_fragment(reference)
+    }
+}
+
+impl AsRef<Url> for GeminiUrl {
+    fn as_ref(&self) -> &Url {
+        &self.0
+    }
+}
+
 impl TryFrom<Url> for GeminiUrl {
     type Error = UrlParseError;
 
     fn try_from(url: Url) -> Result<Self, Self::Error> {
         if url.scheme() != SCHEME_GEMINI {
             Err(UrlParseError::NotGeminiScheme(url))
-        } else if url.has_authority() {
+        } else if request_length(&url) > MAX_URL_LENGTH {
+            Err(UrlParseError::TooLong(request_length(&url)))
+        } else if !url.has_authority() {
             Err(UrlParseError::MissingAuthority(url))
-        } else if url.has_host() {
-            Err(UrlParseError::MissingHost(url))
+        } else if url.host_str().unwrap_or_default().is_empty() {
+            Err(UrlParseError::EmptyHost(url.to_string()))
         } else if url.password().is_some() || !url.username().is_empty() {
             Err(UrlParseError::HasUserInfo(url))
         } else {
@@ -43,7 +262,11 @@ impl FromStr for GeminiUrl {
     type Err = UrlParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        s.parse::<Url>().map(GeminiUrl::try_from)?
+        match s.parse::<Url>() {
+            Ok(url) => GeminiUrl::try_from(url),
+            Err(url::ParseError::EmptyHost) => Err(UrlParseError::EmptyHost(s.to_string())),
+            Err(error) => Err(error.into()),
+        }
     }
 }
 
@@ -52,3 +275,199 @@ impl fmt::Display for GeminiUrl {
         write!(f, "{}", self.0)
     }
 }
+
+/// Serialized as the URL string.
+#[cfg(feature = "serde")]
+impl serde::Serialize for GeminiUrl {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for GeminiUrl {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let url = String::deserialize(deserializer)?;
+        url.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    fn url(s: &str) -> GeminiUrl {
+        s.parse().unwrap()
+    }
+
+    const URLS: [&str; 20] = [
+        "gemini://example.com",
+        "gemini://example.com/",
+        "gemini://example.com/index.gmi",
+        "gemini://example.com/page.gmi",
+        "gemini://example.com/page.gmi?query",
+        "gemini://example.com/page.gmi?other%20query",
+        "gemini://example.com/?query",
+        "gemini://example.com/dir/",
+        "gemini://example.com/dir/index.gmi",
+        "gemini://example.com/dir/page.gmi",
+        "gemini://example.com/dir/sub/",
+        "gemini://example.com/dir/sub/page.gmi",
+        "gemini://example.com/other/page.gmi",
+        "gemini://example.com/a:b/c",
+        "gemini://example.com/dir//page.gmi",
+        "gemini://example.com//page.gmi",
+        "gemini://example.com/%C3%A9t%C3%A9.gmi",
+        "gemini://example.com:1966/page.gmi",
+        "gemini://example.org/page.gmi",
+        "gemini://example.com/page.gmi#fragment",
+    ];
+
+    #[test_case("gemini://example.com/" ; "root")]
+    #[test_case("gemini://example.com" ; "empty path")]
+    #[test_case("gemini://example.com:1966/path?query" ; "port and query")]
+    #[test_case("gemini://[::1]/" ; "ipv6")]
+    #[test_case("gemini://bücher.de/" ; "idn")]
+    #[test_case("gemini://host/#part" ; "fragment")]
+    fn test_parse_valid(input: &str) {
+        let parsed: GeminiUrl = input.parse().expect("should be a valid gemini URL");
+        assert_eq!(parsed.as_url(), &Url::parse(input).unwrap());
+    }
+
+    #[test_case("not a url", UrlParseError::InvalidUrl(url::ParseError::RelativeUrlWithoutBase) ; "invalid")]
+    #[test_case("/relative", UrlParseError::InvalidUrl(url::ParseError::RelativeUrlWithoutBase) ; "relative")]
+    #[test_case("https://example.com/", UrlParseError::NotGeminiScheme(Url::parse("https://example.com/").unwrap()) ; "other scheme")]
+    #[test_case("gemini:path", UrlParseError::MissingAuthority(Url::parse("gemini:path").unwrap()) ; "no authority")]
+    #[test_case("gemini:///path", UrlParseError::EmptyHost("gemini:///path".to_string()) ; "no host")]
+    #[test_case("gemini://:1965/", UrlParseError::EmptyHost("gemini://:1965/".to_string()) ; "empty host with port")]
+    #[test_case("gemini://user@host/", UrlParseError::HasUserInfo(Url::parse("gemini://user@host/").unwrap()) ; "username")]
+    #[test_case("gemini://:pass@host/", UrlParseError::HasUserInfo(Url::parse("gemini://:pass@host/").unwrap()) ; "password")]
+    fn test_parse_invalid(input: &str, expected: UrlParseError) {
+        assert_eq!(input.parse::<GeminiUrl>(), Err(expected));
+    }
+
+    #[test]
+    fn test_max_length() {
+        let prefix = "gemini://example.com/";
+        let longest = format!("{}{}", prefix, "a".repeat(MAX_URL_LENGTH - prefix.len()));
+        assert!(longest.parse::<GeminiUrl>().is_ok());
+        assert_eq!(format!("{}a", longest).parse::<GeminiUrl>(), Err(UrlParseError::TooLong(MAX_URL_LENGTH + 1)));
+        // The fragment is not part of the request.
+        assert!(format!("{}#fragment", longest).parse::<GeminiUrl>().is_ok());
+    }
+
+    #[test_case("gemini://host/page#part", "gemini://host/page" ; "fragment")]
+    #[test_case("gemini://host/page?q#", "gemini://host/page?q" ; "empty fragment")]
+    #[test_case("gemini://host/page", "gemini://host/page" ; "no fragment")]
+    fn test_without_fragment(input: &str, expected: &str) {
+        assert_eq!(url(input).without_fragment().as_url().as_str(), expected);
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!(UrlParseError::TooLong(1030).to_string(), "URL is 1030 bytes long, but may be at most 1024");
+        assert_eq!(
+            "gemini://:1965/".parse::<GeminiUrl>().unwrap_err().to_string(),
+            "URL has an empty host: gemini://:1965/"
+        );
+    }
+
+    #[test_case("gemini://example.com/search", "hello world", "gemini://example.com/search?hello%20world" ; "space")]
+    #[test_case("gemini://example.com/search?old", "a+b&c=d/e?#", "gemini://example.com/search?a%2Bb%26c%3Dd%2Fe%3F%23" ; "reserved characters")]
+    #[test_case("gemini://example.com/", "été 日本", "gemini://example.com/?%C3%A9t%C3%A9%20%E6%97%A5%E6%9C%AC" ; "non ascii")]
+    #[test_case("gemini://example.com/", "a-b.c_d~e", "gemini://example.com/?a-b.c_d~e" ; "unreserved characters")]
+    #[test_case("gemini://example.com/", "", "gemini://example.com/?" ; "empty")]
+    fn test_with_input(base: &str, input: &str, expected: &str) {
+        let with_input = url(base).with_input(input).unwrap();
+        assert_eq!(with_input.as_url().as_str(), expected);
+        assert_eq!(with_input.input().as_deref(), Some(input));
+    }
+
+    #[test]
+    fn test_with_input_too_long() {
+        let base = url("gemini://example.com/?");
+        let fits = "a".repeat(MAX_URL_LENGTH - base.as_url().as_str().len());
+        assert!(base.with_input(&fits).is_ok());
+        assert_eq!(base.with_input(&format!("{} ", fits)), Err(InputTooLong { over: 3 }));
+    }
+
+    #[test_case("gemini://example.com/", None ; "no query")]
+    #[test_case("gemini://example.com/?a+b%20c", Some("a+b c") ; "plus is not a space")]
+    #[test_case("gemini://example.com/?%FF", Some("\u{FFFD}") ; "invalid utf8")]
+    fn test_input(input: &str, expected: Option<&str>) {
+        assert_eq!(url(input).input().as_deref(), expected);
+    }
+
+    #[test_case("gemini://example.com/dir/page.gmi", "gemini://example.com/dir/other.gmi", "page.gmi" ; "same directory")]
+    #[test_case("gemini://example.com/dir/", "gemini://example.com/dir/page.gmi", "./" ; "own directory")]
+    #[test_case("gemini://example.com/dir/index.gmi", "gemini://example.com/dir/page.gmi", "index.gmi" ; "index file")]
+    #[test_case("gemini://example.com/dir/sub/", "gemini://example.com/dir/index.gmi", "sub/" ; "subdirectory")]
+    #[test_case("gemini://example.com/page.gmi", "gemini://example.com/dir/sub/page.gmi", "../../page.gmi" ; "parent directory")]
+    #[test_case("gemini://example.com/other/page.gmi", "gemini://example.com/dir/page.gmi", "../other/page.gmi" ; "sibling directory")]
+    #[test_case("gemini://example.com/page.gmi?q", "gemini://example.com/page.gmi", "page.gmi?q" ; "query")]
+    #[test_case("gemini://example.com/page.gmi", "gemini://example.com/page.gmi?q", "page.gmi" ; "dropped query")]
+    #[test_case("gemini://example.com/page.gmi?q#part", "gemini://example.com/", "page.gmi?q#part" ; "fragment")]
+    #[test_case("gemini://example.com/a:b/c", "gemini://example.com/", "./a:b/c" ; "colon in first segment")]
+    #[test_case("gemini://example.com/dir//page.gmi", "gemini://example.com/dir/x", ".//page.gmi" ; "empty segment")]
+    #[test_case("gemini://example.org/page.gmi", "gemini://example.com/page.gmi", "gemini://example.org/page.gmi" ; "other host")]
+    #[test_case("gemini://example.com:1966/", "gemini://example.com/", "gemini://example.com:1966/" ; "other port")]
+    fn test_make_relative(target: &str, from: &str, expected: &str) {
+        assert_eq!(url(target).make_relative(&url(from)), expected);
+    }
+
+    #[test_case("gemini://example.com/dir/page.gmi?q", "gemini://example.com/", "/dir/page.gmi?q" ; "path and query")]
+    #[test_case("gemini://example.com//page.gmi", "gemini://example.com/", "/.//page.gmi" ; "empty first segment")]
+    #[test_case("gemini://example.org/page.gmi", "gemini://example.com/", "gemini://example.org/page.gmi" ; "other host")]
+    fn test_make_root_relative(target: &str, from: &str, expected: &str) {
+        assert_eq!(url(target).make_root_relative(&url(from)), expected);
+    }
+
+    #[test_case("gemini://example.com", "gemini://example.com/" ; "empty path")]
+    #[test_case("gemini://Example.COM:1965/", "gemini://example.com/" ; "host case and default port")]
+    #[test_case("gemini://example.com:1966/", "gemini://example.com:1966/" ; "other port")]
+    #[test_case("gemini://bücher.de/", "gemini://xn--bcher-kva.de/" ; "idn")]
+    #[test_case("gemini://[::1]:1965/", "gemini://[::1]/" ; "ipv6")]
+    #[test_case("gemini://example.com/a/./b/../c", "gemini://example.com/a/c" ; "dot segments")]
+    #[test_case(
+        "gemini://example.com/%7e%41%3a%c3%a9?q=%7E%2f%",
+        "gemini://example.com/~A%3A%C3%A9?q=~%2F%" ;
+        "percent encoding"
+    )]
+    fn test_normalized(input: &str, expected: &str) {
+        let normalized = url(input).normalized();
+        assert_eq!(normalized.as_url().as_str(), expected);
+        assert_eq!(normalized.normalized(), normalized);
+    }
+
+    #[test_case("gemini://Example.com:1965/", "gemini://example.com/", true)]
+    #[test_case("gemini://example.com", "gemini://example.com/", true)]
+    #[test_case("gemini://example.com/%7Euser/", "gemini://example.com/~user/", true)]
+    #[test_case("gemini://example.com/a", "gemini://example.com/A", false)]
+    #[test_case("gemini://example.com/", "gemini://example.com:1966/", false)]
+    #[test_case("gemini://example.com/?a", "gemini://example.com/?b", false)]
+    fn test_equivalent(a: &str, b: &str, expected: bool) {
+        assert_eq!(url(a).equivalent(&url(b)), expected);
+        assert_eq!(url(b).equivalent(&url(a)), expected);
+    }
+
+    #[test_case("gemini://example.com/", 1965)]
+    #[test_case("gemini://example.com:1965/", 1965)]
+    #[test_case("gemini://example.com:1966/", 1966)]
+    fn test_port_or_default(input: &str, expected: u16) {
+        assert_eq!(url(input).port_or_default(), expected);
+    }
+
+    #[test]
+    fn test_relative_references_resolve_to_target() {
+        for target in URLS.map(url) {
+            for from in URLS.map(url) {
+                for reference in [target.make_relative(&from), target.make_root_relative(&from)] {
+                    let resolved = from.as_url().join(&reference).unwrap();
+                    assert_eq!(&resolved, target.as_url(), "resolving {:?} from {}", reference, from);
+                }
+            }
+        }
+    }
+}
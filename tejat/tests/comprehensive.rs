@@ -13,13 +13,13 @@ use tejat::{
 
 #[test]
 fn test_parse_empty_document() {
-    let out = parse_lines("").unwrap();
+    let out = parse_lines("");
     assert_eq!(out, vec![]);
 }
 
 #[test]
 fn test_parse_big_document() {
-    let out = parse_lines(BIG_DOCUMENT).unwrap();
+    let out = parse_lines(BIG_DOCUMENT);
     let expected = get_parsed_big_document();
     assert_eq!(out, expected);
 }
@@ -27,7 +27,8 @@ fn test_parse_big_document() {
 
 fn get_parsed_big_document() -> Vec<RawLine<'static>> {
     vec![
-        h1!("Test Document"),
+        text!(""),
+        h1!("Test document"),
         text!(""),
         link!("https://example.com" -> "An example HTTPS link"),
         link!("gemini://example.com/test/?query=something&test" -> "An example Gemini link"),
@@ -69,7 +70,6 @@ fn get_parsed_big_document() -> Vec<RawLine<'static>> {
         text!(""),
         list_item!("List item 3"),
         text!(""),
-        text!(""),
     ]
 }
 
@@ -0,0 +1,89 @@
+use std::borrow::Cow;
+
+use pretty_assertions::assert_eq;
+use proptest::prelude::*;
+use tejat::gemtext::{parse_lines, serialize_lines, write_lines, Document, Heading, Link, Preformatted, RawLine};
+
+/// Text that survives having a line type prefix and separating whitespace stripped.
+fn prefixed_text() -> impl Strategy<Value = String> {
+    "([^ \t\r\n][^\r\n]{0,40})?"
+}
+
+/// Text that often starts like another line type.
+fn plain_text() -> impl Strategy<Value = String> {
+    "(#|=>|\\* |\\*|>|```)?[^\r\n]{0,40}\r?"
+}
+
+/// What a line is read back as after serialization: text that looks like another line type gains
+/// a leading space.
+fn reparsed(line: &RawLine<'static>) -> RawLine<'static> {
+    match line {
+        RawLine::Text(text) if !text.is_empty() && parse_lines(text) != [line.clone()] => {
+            RawLine::Text(Cow::Owned(format!(" {}", text)))
+        }
+        line => line.clone(),
+    }
+}
+
+fn link_target() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "(gemini|https?)://[a-z]{1,10}\\.[a-z]{2,3}(/[a-z0-9._-]{0,8}){0,3}(\\?[a-z=&]{1,10})?",
+        "/?[a-z0-9._-]{1,8}(/[a-z0-9._-]{0,8}){0,3}",
+    ]
+}
+
+fn preformatted_body() -> impl Strategy<Value = String> {
+    prop::collection::vec(
+        "[^\r\n]{0,20}\r?".prop_filter("body lines must not toggle preformatted mode", |line| !line.starts_with("```")),
+        0..5,
+    ).prop_map(|lines| lines.join("\n"))
+}
+
+fn raw_line() -> impl Strategy<Value = RawLine<'static>> {
+    prop_oneof![
+        plain_text().prop_map(|text| RawLine::Text(Cow::Owned(text))),
+        prefixed_text().prop_map(|text| RawLine::Blockquote(Cow::Owned(text))),
+        prefixed_text().prop_map(|text| RawLine::ListItem(Cow::Owned(text))),
+        prefixed_text().prop_map(|text| RawLine::Heading(Heading::H1(Cow::Owned(text)))),
+        prefixed_text().prop_map(|text| RawLine::Heading(Heading::H2(Cow::Owned(text)))),
+        prefixed_text().prop_map(|text| RawLine::Heading(Heading::H3(Cow::Owned(text)))),
+        (link_target(), proptest::option::of("[^ \t\r\n][^\r\n]{0,40}")).prop_map(|(target, text)| {
+            RawLine::Link(Link::new(target, text).expect("generated link should be valid").into_static())
+        }),
+        (proptest::option::of("[^\r\n]{1,20}"), preformatted_body()).prop_map(|(alt_text, text)| {
+            RawLine::Preformatted(Preformatted {
+                alt_text: alt_text.map(Cow::Owned),
+                text: Cow::Owned(text),
+            })
+        }),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn test_serialize_then_parse_is_lossless(doc in prop::collection::vec(raw_line(), 0..20)) {
+        let serialized = serialize_lines(&doc);
+        let parsed = parse_lines(&serialized);
+        prop_assert_eq!(parsed, doc.iter().map(reparsed).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_write_matches_serialize(doc in prop::collection::vec(raw_line(), 0..20)) {
+        let mut written = Vec::new();
+        write_lines(&mut written, &doc).expect("writing to a Vec should never fail");
+        prop_assert_eq!(String::from_utf8(written).expect("output should be UTF-8"), serialize_lines(&doc));
+    }
+
+    #[test]
+    fn test_document_into_lines_is_lossless(doc in prop::collection::vec(raw_line(), 0..20)) {
+        prop_assert_eq!(Document::from(doc.clone()).into_lines(), doc);
+    }
+}
+
+#[test]
+fn test_parse_then_serialize_is_canonical() {
+    let input = "#Heading\n=>  gemini://example.com   Example\n>quote\n*  item\n```alt\ncode\n```\n";
+    let expected = "# Heading\n=> gemini://example.com Example\n> quote\n* item\n```alt\ncode\n```\n";
+    let lines = parse_lines(input);
+    assert_eq!(serialize_lines(&lines), expected);
+}
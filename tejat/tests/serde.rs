@@ -0,0 +1,130 @@
+#![cfg(feature = "serde")]
+
+use std::fmt::Debug;
+
+use pretty_assertions::assert_eq;
+use proptest::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use tejat::gemtext::{parse_lines, serialize_lines, Heading, Link, Preformatted, RawLine};
+use tejat::status::{Code, PermanentFailure, Redirect, Status, Success, TemporaryFailure};
+use tejat::uri::GeminiUrl;
+
+/// Checks that `value` serializes to `expected` and deserializes back to itself.
+fn assert_round_trip<T>(value: &T, expected: Value)
+    where T: Debug + PartialEq + Serialize + DeserializeOwned,
+{
+    let serialized = serde_json::to_value(value).unwrap();
+    assert_eq!(serialized, expected);
+    assert_eq!(&serde_json::from_value::<T>(serialized).unwrap(), value);
+}
+
+#[test]
+fn test_lines() {
+    let source = "# Title\ntext\n=> gemini://example.com/ Example\n=> /relative\n* item\n> quote\n```alt\npre\n```\n```\n```\n";
+    let expected = json!([
+        {"type": "heading", "level": 1, "text": "Title"},
+        {"type": "text", "text": "text"},
+        {"type": "link", "url": "gemini://example.com/", "text": "Example"},
+        {"type": "link", "url": "/relative", "text": null},
+        {"type": "list_item", "text": "item"},
+        {"type": "blockquote", "text": "quote"},
+        {"type": "preformatted", "alt_text": "alt", "text": "pre"},
+        {"type": "preformatted", "alt_text": null, "text": ""},
+    ]);
+    let lines: Vec<RawLine<'static>> = parse_lines(source).into_iter().map(RawLine::into_static).collect();
+    assert_round_trip(&lines, expected);
+}
+
+#[test]
+fn test_line_parts() {
+    assert_round_trip(&Heading::H3("Sub".into()), json!({"level": 3, "text": "Sub"}));
+    assert_round_trip(
+        &Link::new("gemini://example.com", Some("Example")).unwrap().into_static(),
+        json!({"url": "gemini://example.com", "text": "Example"}),
+    );
+    assert_round_trip(
+        &Preformatted { alt_text: None, text: "a\nb".into() },
+        json!({"alt_text": null, "text": "a\nb"}),
+    );
+}
+
+#[test]
+fn test_invalid_lines() {
+    assert!(serde_json::from_value::<Heading>(json!({"level": 4, "text": "Deep"})).is_err());
+    assert!(serde_json::from_value::<RawLine>(json!({"type": "table", "text": "x"})).is_err());
+
+    // Nothing may add lines of its own when serialized.
+    let injections = [
+        json!({"type": "link", "url": "a b\n# x", "text": null}),
+        json!({"type": "link", "url": "/a", "text": "x\n# y"}),
+        json!({"type": "text", "text": "=> x\n* y"}),
+        json!({"type": "text", "text": "a\rb"}),
+        json!({"type": "list_item", "text": "a\n> b"}),
+        json!({"type": "blockquote", "text": "a\n# b"}),
+        json!({"type": "heading", "level": 1, "text": "a\n=> /b"}),
+        json!({"type": "preformatted", "alt_text": "a\nb", "text": ""}),
+        json!({"type": "preformatted", "alt_text": null, "text": "a\n```\n# b"}),
+    ];
+    for line in injections {
+        assert!(serde_json::from_value::<RawLine>(line.clone()).is_err(), "deserialized {}", line);
+    }
+    assert!(serde_json::from_value::<Link>(json!({"url": "a b", "text": null})).is_err());
+}
+
+#[test]
+fn test_statuses() {
+    assert_round_trip(&Code::NOT_FOUND, json!(51));
+    assert_round_trip(&Status::not_found("Not here").unwrap(), json!({"code": 51, "meta": "Not here"}));
+    assert_round_trip(&Status::slow_down(30), json!({"code": 44, "meta": "30"}));
+    assert_round_trip(
+        &Status::redirect_permanent("gemini://example.com/new".parse::<GeminiUrl>().unwrap()).unwrap(),
+        json!({"code": 31, "meta": "gemini://example.com/new"}),
+    );
+
+    let success: Success = "20 text/gemini; lang=en,fr".parse().unwrap();
+    assert_round_trip(&success, json!({"code": 20, "meta": "text/gemini; lang=en,fr"}));
+    let redirect: Redirect = "30 /relative".parse().unwrap();
+    assert_round_trip(&redirect, json!({"code": 30, "meta": "/relative"}));
+    let failure: PermanentFailure = "59 Bad request".parse().unwrap();
+    assert_round_trip(&failure, json!({"code": 59, "meta": "Bad request"}));
+}
+
+#[test]
+fn test_invalid_statuses() {
+    assert!(serde_json::from_value::<Code>(json!(99)).is_err());
+    assert!(serde_json::from_value::<Status>(json!({"code": 44, "meta": "soon"})).is_err());
+    assert!(serde_json::from_value::<TemporaryFailure>(json!({"code": 51, "meta": "Not found"})).is_err());
+
+    // A meta that would not be read back as the same header.
+    for meta in ["a\r\n20 text/html".to_string(), "a".repeat(2000)] {
+        assert!(serde_json::from_value::<Status>(json!({"code": 51, "meta": meta})).is_err());
+        assert!(serde_json::from_value::<PermanentFailure>(json!({"code": 51, "meta": meta})).is_err());
+    }
+}
+
+#[test]
+fn test_empty_success_meta() {
+    let json = json!({"code": 20, "meta": ""});
+    let status: Status = serde_json::from_value(json.clone()).unwrap();
+    let success: Success = serde_json::from_value(json).unwrap();
+    assert_eq!(status, Status::Success(success));
+}
+
+#[test]
+fn test_gemini_url() {
+    let url: GeminiUrl = "gemini://example.com/path?query".parse().unwrap();
+    assert_round_trip(&url, json!("gemini://example.com/path?query"));
+    assert!(serde_json::from_value::<GeminiUrl>(json!("https://example.com/")).is_err());
+}
+
+proptest! {
+    #[test]
+    fn test_lines_round_trip(source in "(([#*>`=]{0,3}[ ]?[a-z /:.]{0,10})\n){0,10}") {
+        let lines = parse_lines(&source);
+        let json = serde_json::to_string(&lines).unwrap();
+        let deserialized: Vec<RawLine> = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(serialize_lines(&deserialized), serialize_lines(&lines));
+        prop_assert_eq!(deserialized, lines);
+    }
+}